@@ -0,0 +1,820 @@
+use std::error::Error;
+use std::process::exit;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use serde_json::{json, Value};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    instruction::{Instruction, InstructionError},
+    pubkey::Pubkey,
+    signature::{read_keypair_file, Keypair, Signature, Signer},
+    system_instruction, system_program,
+    transaction::{Transaction, TransactionError},
+};
+
+use solana_multisig::client;
+use solana_multisig::error::Error as MultisigError;
+use solana_multisig::instruction::ProtectedAccountConfig;
+use solana_multisig::state::{
+    unpack_account_data, AccountType, ApprovalMessage, GroupAccount, GroupData, GroupMember,
+    ProgramPolicy, ProposalConfig, ProposalData, ProposalExpiry, Versioned,
+};
+
+mod output;
+
+use output::OutputFormat;
+
+type CliResult<T> = Result<T, Box<dyn Error>>;
+
+struct Config {
+    rpc_client: RpcClient,
+    program_id: Pubkey,
+    keypair_path: String,
+    output: OutputFormat,
+}
+
+impl Config {
+    fn keypair(&self) -> CliResult<Keypair> {
+        read_keypair_file(&self.keypair_path)
+            .map_err(|err| format!("can't read keypair {}: {}", self.keypair_path, err).into())
+    }
+
+    /// Sign instructions with the keypair, which also pays the fees, and send them.
+    fn send(&self, instructions: &[Instruction], signer: &Keypair) -> CliResult<Signature> {
+        let (recent_blockhash, _) = self.rpc_client.get_recent_blockhash()?;
+        let transaction = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&signer.pubkey()),
+            &[signer],
+            recent_blockhash,
+        );
+        self.rpc_client
+            .send_and_confirm_transaction(&transaction)
+            .map_err(|err| match err.get_transaction_error() {
+                Some(TransactionError::InstructionError(_, InstructionError::Custom(code))) => {
+                    match MultisigError::from_custom_code(code) {
+                        Some(error) => format!("transaction failed: {}", error).into(),
+                        None => err.into(),
+                    }
+                }
+                _ => err.into(),
+            })
+    }
+
+    fn read_group(&self, key: &Pubkey) -> CliResult<GroupAccount> {
+        self.read_account_data(key, AccountType::Group)
+    }
+
+    fn read_proposal(&self, key: &Pubkey) -> CliResult<ProposalData> {
+        self.read_account_data(key, AccountType::Proposal)
+    }
+
+    fn read_account_data<T: Versioned>(
+        &self,
+        key: &Pubkey,
+        account_type: AccountType,
+    ) -> CliResult<T> {
+        let account = self.rpc_client.get_account(key)?;
+        if account.owner != self.program_id {
+            return Err(format!("account {} is not owned by the multisig program", key).into());
+        }
+        unpack_account_data(account_type, &account.data)
+            .map_err(|err| format!("account {}: {}", key, err).into())
+    }
+}
+
+fn pubkey_of(matches: &ArgMatches, name: &str) -> CliResult<Pubkey> {
+    let value = matches
+        .value_of(name)
+        .ok_or_else(|| format!("missing {}", name))?;
+    Pubkey::from_str(value).map_err(|err| format!("invalid {} {}: {}", name, value, err).into())
+}
+
+fn value_of<T>(matches: &ArgMatches, name: &str) -> CliResult<Option<T>>
+where
+    T: FromStr,
+    T::Err: std::fmt::Display,
+{
+    matches
+        .value_of(name)
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|err| format!("invalid {} {}: {}", name, value, err).into())
+        })
+        .transpose()
+}
+
+fn parse_member_signature(value: &str) -> CliResult<(Pubkey, [u8; 64])> {
+    let (key, signature) = match value.find('=') {
+        Some(pos) => (&value[..pos], &value[pos + 1..]),
+        None => return Err(format!("invalid member signature {}", value).into()),
+    };
+    let key = Pubkey::from_str(key).map_err(|err| format!("invalid member {}: {}", key, err))?;
+    let signature = Signature::from_str(signature)
+        .map_err(|err| format!("invalid signature of {}: {}", key, err))?;
+    let mut bytes = [0; 64];
+    bytes.copy_from_slice(signature.as_ref());
+    Ok((key, bytes))
+}
+
+fn pubkeys_of(matches: &ArgMatches, name: &str) -> CliResult<Vec<Pubkey>> {
+    matches
+        .values_of(name)
+        .into_iter()
+        .flatten()
+        .map(|value| {
+            Pubkey::from_str(value)
+                .map_err(|err| format!("invalid {} {}: {}", name, value, err).into())
+        })
+        .collect()
+}
+
+fn program_policy_of(matches: &ArgMatches) -> CliResult<ProgramPolicy> {
+    if matches.is_present("allow_program") {
+        Ok(ProgramPolicy::Allow(pubkeys_of(matches, "allow_program")?))
+    } else if matches.is_present("deny_program") {
+        Ok(ProgramPolicy::Deny(pubkeys_of(matches, "deny_program")?))
+    } else {
+        Ok(ProgramPolicy::Any)
+    }
+}
+
+fn parse_member(value: &str) -> CliResult<GroupMember> {
+    let (key, weight) = match value.find(':') {
+        Some(pos) => (&value[..pos], &value[pos + 1..]),
+        None => (value, "1"),
+    };
+    Ok(GroupMember {
+        public_key: Pubkey::from_str(key)
+            .map_err(|err| format!("invalid member {}: {}", key, err))?,
+        weight: weight
+            .parse()
+            .map_err(|err| format!("invalid weight of {}: {}", key, err))?,
+    })
+}
+
+fn parse_expiry(matches: &ArgMatches) -> CliResult<Option<ProposalExpiry>> {
+    if let Some(slot) = value_of(matches, "expiry_slot")? {
+        return Ok(Some(ProposalExpiry::Slot(slot)));
+    }
+    Ok(value_of(matches, "expiry_timestamp")?.map(ProposalExpiry::UnixTimestamp))
+}
+
+fn init(config: &Config, matches: &ArgMatches) -> CliResult<Value> {
+    let payer = config.keypair()?;
+    let members = matches
+        .values_of("member")
+        .into_iter()
+        .flatten()
+        .map(parse_member)
+        .collect::<CliResult<Vec<_>>>()?;
+    let group_data = GroupData {
+        members,
+        threshold: value_of(matches, "threshold")?.unwrap_or_default(),
+        execution_delay: value_of(matches, "execution_delay")?.unwrap_or_default(),
+        threshold_rules: Vec::new(),
+        program_policy: program_policy_of(matches)?,
+    };
+    let space: u64 = value_of(matches, "space")?.unwrap_or_default();
+    let protected_account_config =
+        value_of(matches, "protected_lamports")?.map(|lamports| ProtectedAccountConfig {
+            lamports,
+            space: 0,
+            owner: system_program::id(),
+        });
+
+    let group_key = client::group_address(&config.program_id, &group_data);
+    let protected_key = client::protected_address(&config.program_id, &group_key);
+    let group_size = client::group_account_space(&group_data, 1);
+    let lamports = config
+        .rpc_client
+        .get_minimum_balance_for_rent_exemption(space.max(group_size) as usize)?;
+
+    let instruction = client::init(
+        &config.program_id,
+        &payer.pubkey(),
+        group_data,
+        lamports,
+        space,
+        protected_account_config,
+    );
+    let signature = config.send(&[instruction], &payer)?;
+
+    Ok(json!({
+        "signature": signature.to_string(),
+        "group": group_key.to_string(),
+        "protected_account": protected_key.to_string(),
+    }))
+}
+
+fn propose(config: &Config, matches: &ArgMatches) -> CliResult<Value> {
+    let proposer = config.keypair()?;
+    let group_key = pubkey_of(matches, "group")?;
+    let group = config.read_group(&group_key)?;
+
+    let instructions = match matches.subcommand() {
+        ("transfer", Some(matches)) => {
+            let vault: u16 = value_of(matches, "vault")?.unwrap_or_default();
+            let source = client::vault_address(&config.program_id, &group_key, vault);
+            let instruction = system_instruction::transfer(
+                &source,
+                &pubkey_of(matches, "destination")?,
+                value_of(matches, "lamports")?.unwrap_or_default(),
+            );
+            vec![client::proposed_instruction(instruction, vec![vault])]
+        }
+        ("approve", Some(matches)) => {
+            let proposal_key = pubkey_of(matches, "proposal")?;
+            let proposal = config.read_proposal(&proposal_key)?;
+            vec![client::approve_as_group(
+                &config.program_id,
+                &group_key,
+                &proposal.config.group,
+                &proposal_key,
+            )]
+        }
+        _ => return Err("missing proposal kind".into()),
+    };
+    let salt = match value_of(matches, "salt")? {
+        Some(salt) => salt,
+        None => SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos() as u64,
+    };
+    let expiry = parse_expiry(matches)?;
+
+    let proposal_config = ProposalConfig {
+        group: group_key,
+        instructions: instructions.clone(),
+        author: proposer.pubkey(),
+        salt,
+        expiry,
+    };
+    let proposal_key =
+        client::proposal_address(&config.program_id, &group_key, group.proposal_count);
+    let proposal_size =
+        client::proposal_account_space(&proposal_config, group.data.members.len()) as usize;
+    let lamports = config
+        .rpc_client
+        .get_minimum_balance_for_rent_exemption(proposal_size)?;
+
+    let instruction = client::propose(
+        &config.program_id,
+        &proposer.pubkey(),
+        &group_key,
+        group.proposal_count,
+        instructions,
+        lamports,
+        salt,
+        expiry,
+    );
+    let signature = config.send(&[instruction], &proposer)?;
+
+    Ok(json!({
+        "signature": signature.to_string(),
+        "proposal": proposal_key.to_string(),
+    }))
+}
+
+fn approve(config: &Config, matches: &ArgMatches) -> CliResult<Value> {
+    let approver = config.keypair()?;
+    let proposal_key = pubkey_of(matches, "proposal")?;
+    let proposal = config.read_proposal(&proposal_key)?;
+
+    let signatures = matches
+        .values_of("member_signature")
+        .into_iter()
+        .flatten()
+        .map(parse_member_signature)
+        .collect::<CliResult<Vec<_>>>()?;
+    // With member signatures the keypair only pays for the transaction.
+    let instructions = if signatures.is_empty() {
+        vec![client::approve(
+            &config.program_id,
+            &approver.pubkey(),
+            &proposal.config.group,
+            &proposal_key,
+        )]
+    } else {
+        client::approve_with_signatures(
+            &config.program_id,
+            &proposal_key,
+            &proposal.config,
+            proposal.approval_nonce,
+            &signatures,
+        )
+    };
+    let signature = config.send(&instructions, &approver)?;
+
+    let proposal = config.read_proposal(&proposal_key)?;
+    Ok(json!({
+        "signature": signature.to_string(),
+        "proposal": proposal_key.to_string(),
+        "status": format!("{:?}", proposal.state.status()),
+        "current_weight": proposal.state.current_weight(),
+    }))
+}
+
+fn sign_approval(config: &Config, matches: &ArgMatches) -> CliResult<Value> {
+    let member = config.keypair()?;
+    let proposal_key = pubkey_of(matches, "proposal")?;
+    let proposal = config.read_proposal(&proposal_key)?;
+
+    let message = ApprovalMessage::new(
+        &config.program_id,
+        &proposal_key,
+        &proposal.config,
+        proposal.approval_nonce,
+    )?;
+    let signature = member.sign_message(&message.to_bytes()?);
+    Ok(json!({
+        "message": message.to_string(),
+        "member_signature": format!("{}={}", member.pubkey(), signature),
+    }))
+}
+
+fn execute(config: &Config, matches: &ArgMatches) -> CliResult<Value> {
+    let executor = config.keypair()?;
+    let proposal_key = pubkey_of(matches, "proposal")?;
+    let proposal = config.read_proposal(&proposal_key)?;
+
+    if !matches.is_present("receipt") {
+        let instruction = client::execute(
+            &config.program_id,
+            &executor.pubkey(),
+            &proposal_key,
+            &proposal.config,
+        );
+        let signature = config.send(&[instruction], &executor)?;
+        return Ok(json!({
+            "signature": signature.to_string(),
+            "proposal": proposal_key.to_string(),
+        }));
+    }
+
+    let lamports = config
+        .rpc_client
+        .get_minimum_balance_for_rent_exemption(client::receipt_account_space() as usize)?;
+    let instruction = client::execute_with_receipt(
+        &config.program_id,
+        &executor.pubkey(),
+        &proposal_key,
+        &proposal.config,
+        lamports,
+    );
+    let signature = config.send(&[instruction], &executor)?;
+    Ok(json!({
+        "signature": signature.to_string(),
+        "proposal": proposal_key.to_string(),
+        "receipt": client::receipt_address(&config.program_id, &proposal_key).to_string(),
+    }))
+}
+
+fn close(config: &Config, matches: &ArgMatches) -> CliResult<Value> {
+    let closer = config.keypair()?;
+    let proposal_key = pubkey_of(matches, "proposal")?;
+    let destination = match matches.value_of("destination") {
+        Some(_) => pubkey_of(matches, "destination")?,
+        None => config.read_proposal(&proposal_key)?.config.author,
+    };
+
+    let receipt = matches.is_present("receipt");
+
+    let instruction = client::close_proposal(
+        &config.program_id,
+        &closer.pubkey(),
+        &proposal_key,
+        &destination,
+        receipt,
+    );
+    let signature = config.send(&[instruction], &closer)?;
+
+    let mut output = json!({
+        "signature": signature.to_string(),
+        "proposal": proposal_key.to_string(),
+        "destination": destination.to_string(),
+    });
+    if receipt {
+        output["receipt"] = client::receipt_address(&config.program_id, &proposal_key)
+            .to_string()
+            .into();
+    }
+    Ok(output)
+}
+
+fn migrate(config: &Config, matches: &ArgMatches) -> CliResult<Value> {
+    let payer = config.keypair()?;
+    let account_key = pubkey_of(matches, "account")?;
+
+    let instruction = client::migrate(&config.program_id, &account_key, Some(&payer.pubkey()));
+    let signature = config.send(&[instruction], &payer)?;
+
+    Ok(json!({
+        "signature": signature.to_string(),
+        "account": account_key.to_string(),
+    }))
+}
+
+fn show_group(config: &Config, matches: &ArgMatches) -> CliResult<Value> {
+    let group_key = pubkey_of(matches, "group")?;
+    let group_account = config.read_group(&group_key)?;
+    let group = &group_account.data;
+
+    let members: Vec<Value> = group
+        .members
+        .iter()
+        .map(|member| {
+            json!({
+                "public_key": member.public_key.to_string(),
+                "weight": member.weight,
+            })
+        })
+        .collect();
+    Ok(json!({
+        "group": group_key.to_string(),
+        "protected_account": client::protected_address(&config.program_id, &group_key).to_string(),
+        "threshold": group.threshold,
+        "execution_delay": group.execution_delay,
+        "members": members,
+        "program_policy": program_policy_json(&group.program_policy),
+        "config_version": group_account.config_version,
+        "proposal_count": group_account.proposal_count,
+        "proposals": (0..group_account.proposal_count)
+            .map(|index| client::proposal_address(&config.program_id, &group_key, index).to_string())
+            .collect::<Vec<_>>(),
+    }))
+}
+
+fn program_policy_json(policy: &ProgramPolicy) -> Value {
+    let keys =
+        |programs: &[Pubkey]| -> Vec<String> { programs.iter().map(ToString::to_string).collect() };
+    match policy {
+        ProgramPolicy::Any => json!("any"),
+        ProgramPolicy::Allow(programs) => json!({ "allow": keys(programs) }),
+        ProgramPolicy::Deny(programs) => json!({ "deny": keys(programs) }),
+    }
+}
+
+fn show_proposal(config: &Config, matches: &ArgMatches) -> CliResult<Value> {
+    let proposal_key = pubkey_of(matches, "proposal")?;
+    let proposal = config.read_proposal(&proposal_key)?;
+    let group_account = config.read_group(&proposal.config.group)?;
+    let stale = group_account
+        .check_proposal_version(proposal.group_version)
+        .is_err();
+    let group = &group_account.data;
+    let threshold = proposal.threshold(group);
+    let ProposalData {
+        config: proposal_config,
+        state,
+        ..
+    } = proposal;
+
+    // Votes of stale proposals refer to members the group had before its update.
+    let members_where = |voted: &dyn Fn(usize) -> bool| -> Vec<String> {
+        group
+            .members
+            .iter()
+            .enumerate()
+            .filter(|(idx, _)| !stale && voted(*idx))
+            .map(|(_, member)| member.public_key.to_string())
+            .collect()
+    };
+    let expiry = match proposal_config.expiry {
+        Some(ProposalExpiry::Slot(slot)) => json!({ "slot": slot }),
+        Some(ProposalExpiry::UnixTimestamp(timestamp)) => json!({ "unix_timestamp": timestamp }),
+        None => Value::Null,
+    };
+    let instructions: Vec<Value> = proposal_config
+        .instructions
+        .iter()
+        .map(|instruction| {
+            let accounts: Vec<Value> = instruction
+                .accounts
+                .iter()
+                .map(|account| {
+                    json!({
+                        "pubkey": account.pubkey.to_string(),
+                        "is_signer": account.is_signer,
+                        "is_writable": account.is_writable,
+                    })
+                })
+                .collect();
+            json!({
+                "program_id": instruction.program_id.to_string(),
+                "accounts": accounts,
+                "data": output::hex(&instruction.data),
+                "vaults": instruction.vaults,
+            })
+        })
+        .collect();
+
+    Ok(json!({
+        "proposal": proposal_key.to_string(),
+        "group": proposal_config.group.to_string(),
+        "author": proposal_config.author.to_string(),
+        "salt": proposal_config.salt,
+        "expiry": expiry,
+        "status": format!("{:?}", state.status()),
+        "stale": stale,
+        "current_weight": state.current_weight(),
+        "rejected_weight": state.rejected_weight(),
+        "threshold": threshold,
+        "approved_at": state.approved_at(),
+        "approved_by": members_where(&|idx| state.is_approved_by(idx)),
+        "rejected_by": members_where(&|idx| state.is_rejected_by(idx)),
+        "instructions": instructions,
+    }))
+}
+
+fn app<'a, 'b>() -> App<'a, 'b> {
+    let proposal_arg = Arg::with_name("proposal")
+        .long("proposal")
+        .value_name("PUBKEY")
+        .takes_value(true)
+        .required(true)
+        .help("Proposal account");
+
+    App::new("multisig")
+        .about("Manage multisig groups and proposals")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .arg(
+            Arg::with_name("url")
+                .long("url")
+                .short("u")
+                .global(true)
+                .takes_value(true)
+                .default_value("http://localhost:8899")
+                .help("RPC URL of the cluster"),
+        )
+        .arg(
+            Arg::with_name("program_id")
+                .long("program-id")
+                .global(true)
+                .takes_value(true)
+                .value_name("PUBKEY")
+                .help("Multisig program id"),
+        )
+        .arg(
+            Arg::with_name("keypair")
+                .long("keypair")
+                .short("k")
+                .global(true)
+                .takes_value(true)
+                .value_name("PATH")
+                .help("Keypair file signing and paying for transactions [default: ~/.config/solana/id.json]"),
+        )
+        .arg(
+            Arg::with_name("output")
+                .long("output")
+                .global(true)
+                .takes_value(true)
+                .possible_values(&["display", "json"])
+                .default_value("display")
+                .help("Output format"),
+        )
+        .subcommand(
+            SubCommand::with_name("init")
+                .about("Create a group")
+                .arg(
+                    Arg::with_name("member")
+                        .long("member")
+                        .value_name("PUBKEY[:WEIGHT]")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .required(true)
+                        .help("Group member with its weight, 1 if omitted"),
+                )
+                .arg(
+                    Arg::with_name("threshold")
+                        .long("threshold")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Total weight of approvals needed to execute a proposal"),
+                )
+                .arg(
+                    Arg::with_name("execution_delay")
+                        .long("execution-delay")
+                        .value_name("SECONDS")
+                        .takes_value(true)
+                        .help("Time between a proposal approval and its execution"),
+                )
+                .arg(
+                    Arg::with_name("allow_program")
+                        .long("allow-program")
+                        .value_name("PROGRAM_ID")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .conflicts_with("deny_program")
+                        .help("Program proposals may invoke, any program if none given"),
+                )
+                .arg(
+                    Arg::with_name("deny_program")
+                        .long("deny-program")
+                        .value_name("PROGRAM_ID")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("Program proposals may not invoke"),
+                )
+                .arg(
+                    Arg::with_name("space")
+                        .long("space")
+                        .takes_value(true)
+                        .help("Group account size, leaves room for adding members later"),
+                )
+                .arg(
+                    Arg::with_name("protected_lamports")
+                        .long("protected-lamports")
+                        .value_name("LAMPORTS")
+                        .takes_value(true)
+                        .help("Create the protected account funded with this amount"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("propose")
+                .about("Propose instructions to be executed by a group")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .arg(
+                    Arg::with_name("group")
+                        .long("group")
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Group account"),
+                )
+                .arg(
+                    Arg::with_name("salt")
+                        .long("salt")
+                        .takes_value(true)
+                        .help("Salt making the proposal unique [default: current time]"),
+                )
+                .arg(
+                    Arg::with_name("expiry_slot")
+                        .long("expiry-slot")
+                        .value_name("SLOT")
+                        .takes_value(true)
+                        .conflicts_with("expiry_timestamp")
+                        .help("Slot after which the proposal can't be approved"),
+                )
+                .arg(
+                    Arg::with_name("expiry_timestamp")
+                        .long("expiry-timestamp")
+                        .value_name("UNIX_TIMESTAMP")
+                        .takes_value(true)
+                        .help("Time after which the proposal can't be approved"),
+                )
+                .subcommand(
+                    SubCommand::with_name("transfer")
+                        .about("Transfer lamports from a group vault")
+                        .arg(
+                            Arg::with_name("destination")
+                                .long("destination")
+                                .value_name("PUBKEY")
+                                .takes_value(true)
+                                .required(true)
+                                .help("Account receiving lamports"),
+                        )
+                        .arg(
+                            Arg::with_name("lamports")
+                                .long("lamports")
+                                .takes_value(true)
+                                .required(true)
+                                .help("Amount to transfer"),
+                        )
+                        .arg(
+                            Arg::with_name("vault")
+                                .long("vault")
+                                .value_name("INDEX")
+                                .takes_value(true)
+                                .help("Vault to transfer from, 0 is the protected account"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("approve")
+                        .about("Approve a proposal of a group having this group as a member")
+                        .arg(proposal_arg.clone()),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("approve")
+                .about("Approve a proposal")
+                .arg(proposal_arg.clone())
+                .arg(
+                    Arg::with_name("member_signature")
+                        .long("member-signature")
+                        .value_name("PUBKEY=SIGNATURE")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("Approve for members who signed with sign-approval instead"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("sign-approval")
+                .about("Sign the approval message of a proposal, to be passed to approve")
+                .arg(proposal_arg.clone()),
+        )
+        .subcommand(
+            SubCommand::with_name("execute")
+                .about("Execute an approved proposal")
+                .arg(proposal_arg.clone())
+                .arg(
+                    Arg::with_name("receipt")
+                        .long("receipt")
+                        .help("Keep a receipt account recording the execution, paid by the keypair"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("close")
+                .about("Close a proposal and reclaim its lamports")
+                .arg(proposal_arg.clone())
+                .arg(
+                    Arg::with_name("destination")
+                        .long("destination")
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .help("Account receiving lamports [default: proposal author]"),
+                )
+                .arg(
+                    Arg::with_name("receipt")
+                        .long("receipt")
+                        .help("Keep a receipt account recording how the proposal ended, paid from its lamports"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("migrate")
+                .about("Rewrite a group or proposal account in the current layout")
+                .arg(
+                    Arg::with_name("account")
+                        .value_name("PUBKEY")
+                        .required(true)
+                        .help("Group or proposal account"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("show-group")
+                .about("Show group members and threshold")
+                .arg(
+                    Arg::with_name("group")
+                        .value_name("PUBKEY")
+                        .required(true)
+                        .help("Group account"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("show-proposal")
+                .about("Show proposed instructions and votes")
+                .arg(
+                    Arg::with_name("proposal")
+                        .value_name("PUBKEY")
+                        .required(true)
+                        .help("Proposal account"),
+                ),
+        )
+}
+
+fn run(matches: &ArgMatches) -> CliResult<()> {
+    let (command, command_matches) = matches.subcommand();
+    let command_matches = command_matches.ok_or("missing command")?;
+
+    let keypair_path = match command_matches.value_of("keypair") {
+        Some(path) => path.to_owned(),
+        None => format!("{}/.config/solana/id.json", std::env::var("HOME")?),
+    };
+    let config = Config {
+        rpc_client: RpcClient::new_with_commitment(
+            command_matches.value_of("url").unwrap().to_owned(),
+            CommitmentConfig::confirmed(),
+        ),
+        program_id: pubkey_of(command_matches, "program_id")?,
+        keypair_path,
+        output: command_matches.value_of("output").unwrap().parse()?,
+    };
+
+    let result = match command {
+        "init" => init(&config, command_matches),
+        "propose" => propose(&config, command_matches),
+        "approve" => approve(&config, command_matches),
+        "sign-approval" => sign_approval(&config, command_matches),
+        "execute" => execute(&config, command_matches),
+        "close" => close(&config, command_matches),
+        "migrate" => migrate(&config, command_matches),
+        "show-group" => show_group(&config, command_matches),
+        "show-proposal" => show_proposal(&config, command_matches),
+        _ => unreachable!(),
+    }?;
+    config.output.print(&result);
+    Ok(())
+}
+
+fn main() {
+    if let Err(err) = run(&app().get_matches()) {
+        eprintln!("error: {}", err);
+        exit(1);
+    }
+}
@@ -0,0 +1,91 @@
+use std::fmt::Write;
+use std::str::FromStr;
+
+use serde_json::Value;
+
+/// How command results are printed.
+#[derive(Clone, Copy)]
+pub enum OutputFormat {
+    /// Indented `key: value` lines.
+    Display,
+    /// Pretty printed JSON, for scripts.
+    Json,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "display" => Ok(OutputFormat::Display),
+            "json" => Ok(OutputFormat::Json),
+            _ => Err(format!("unknown output format {}", value)),
+        }
+    }
+}
+
+impl OutputFormat {
+    pub fn print(self, value: &Value) {
+        match self {
+            OutputFormat::Display => {
+                let mut out = String::new();
+                write_display(&mut out, value, 0);
+                print!("{}", out);
+            }
+            OutputFormat::Json => println!("{:#}", value),
+        }
+    }
+}
+
+fn write_display(out: &mut String, value: &Value, indent: usize) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                match value {
+                    Value::Object(_) | Value::Array(_) => {
+                        writeln!(out, "{:indent$}{}:", "", key, indent = indent).unwrap();
+                        write_display(out, value, indent + 2);
+                    }
+                    _ => {
+                        writeln!(
+                            out,
+                            "{:indent$}{}: {}",
+                            "",
+                            key,
+                            scalar(value),
+                            indent = indent
+                        )
+                        .unwrap();
+                    }
+                }
+            }
+        }
+        Value::Array(values) => {
+            for value in values {
+                match value {
+                    Value::Object(_) | Value::Array(_) => {
+                        writeln!(out, "{:indent$}-", "", indent = indent).unwrap();
+                        write_display(out, value, indent + 2);
+                    }
+                    _ => {
+                        writeln!(out, "{:indent$}- {}", "", scalar(value), indent = indent).unwrap()
+                    }
+                }
+            }
+        }
+        _ => writeln!(out, "{:indent$}{}", "", scalar(value), indent = indent).unwrap(),
+    }
+}
+
+fn scalar(value: &Value) -> String {
+    match value {
+        Value::String(value) => value.clone(),
+        Value::Null => "none".to_owned(),
+        value => value.to_string(),
+    }
+}
+
+/// Hex encoding of instruction data.
+pub fn hex(data: &[u8]) -> String {
+    data.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
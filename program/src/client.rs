@@ -0,0 +1,570 @@
+//! Instruction builders and account addresses for programs and services talking to the multisig.
+
+use borsh::BorshSerialize;
+use solana_program::{
+    hash::{hash, Hash},
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    system_program, sysvar,
+};
+
+use crate::ed25519;
+use crate::instruction::{
+    AppendToDraftInstruction, ApproveInstruction, ApproveWithSignaturesInstruction,
+    CloseProposalInstruction, CreateDraftInstruction, CreateSpendingLimitInstruction,
+    CreateVaultInstruction, ExecuteInstruction, ExecuteWithReceiptInstruction,
+    FinalizeDraftInstruction, InitInstruction, MigrateInstruction, MultiSigInstruction,
+    ProposeInstruction, ProtectedAccountConfig, RejectInstruction, RemoveSpendingLimitInstruction,
+    RevokeApprovalInstruction, SpendWithinLimitInstruction, UpdateGroupInstruction,
+};
+use crate::processor::{pda_tag, vault_seeds};
+use crate::state::{
+    ApprovalMessage, GroupAccount, GroupData, GroupVault, ProposalConfig, ProposalData,
+    ProposalExpiry, ProposalReceipt, ProposalSeed, ProposalState, ProposedAccountMeta,
+    ProposedInstruction, SpendingLimitConfig, ACCOUNT_HEADER_LEN,
+};
+use crate::token;
+
+/// Address of the group account holding `group_data` at creation.
+/// Group updates keep the address, so it has to be remembered after the first update.
+pub fn group_address(program_id: &Pubkey, group_data: &GroupData) -> Pubkey {
+    let seed = hash(&group_data.try_to_vec().unwrap());
+    Pubkey::find_program_address(&[pda_tag::GROUP, seed.as_ref()], program_id).0
+}
+
+/// Address of the group proposal with the given index. Proposals are numbered from zero
+/// in the order they are created, so a group's proposals are found by walking indices up to
+/// its `proposal_count`. Closed proposals leave gaps.
+pub fn proposal_address(program_id: &Pubkey, group: &Pubkey, index: u64) -> Pubkey {
+    Pubkey::find_program_address(
+        &[pda_tag::PROPOSAL, group.as_ref(), &index.to_le_bytes()],
+        program_id,
+    )
+    .0
+}
+
+/// Address of the group spending limit with the given index.
+pub fn spending_limit_address(program_id: &Pubkey, group: &Pubkey, index: u16) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            pda_tag::SPENDING_LIMIT,
+            group.as_ref(),
+            &index.to_le_bytes(),
+        ],
+        program_id,
+    )
+    .0
+}
+
+/// Address of the receipt kept for the proposal executed with [execute_with_receipt].
+pub fn receipt_address(program_id: &Pubkey, proposal: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[pda_tag::RECEIPT, proposal.as_ref()], program_id).0
+}
+
+/// Space taken by a receipt account.
+pub fn receipt_account_space() -> u64 {
+    (ProposalReceipt::LEN + ACCOUNT_HEADER_LEN) as u64
+}
+
+/// Space taken by a group account holding `group_data` and `vaults` created vaults,
+/// counting the protected account.
+pub fn group_account_space(group_data: &GroupData, vaults: usize) -> u64 {
+    let group = GroupAccount {
+        seed: Hash::default(),
+        bump: 0,
+        vaults: vec![GroupVault { index: 0, bump: 0 }; vaults.max(1)],
+        data: group_data.clone(),
+        proposal_count: 0,
+        config_version: 0,
+    };
+    group.try_to_vec().unwrap().len() as u64 + ACCOUNT_HEADER_LEN as u64
+}
+
+/// Space taken by a proposal account holding `config`, for a group of `member_count` members.
+pub fn proposal_account_space(config: &ProposalConfig, member_count: usize) -> u64 {
+    let proposal = ProposalData {
+        seed: ProposalSeed::Index(0),
+        bump: 0,
+        config: config.clone(),
+        state: ProposalState::new(member_count),
+        threshold: 0,
+        group_version: 0,
+        approval_nonce: 0,
+    };
+    proposal.try_to_vec().unwrap().len() as u64 + ACCOUNT_HEADER_LEN as u64
+}
+
+/// Address of the group's protected account, i.e. vault 0.
+pub fn protected_address(program_id: &Pubkey, group: &Pubkey) -> Pubkey {
+    vault_address(program_id, group, 0)
+}
+
+/// Address of the group vault with the given index.
+pub fn vault_address(program_id: &Pubkey, group: &Pubkey, index: u16) -> Pubkey {
+    let index_bytes = index.to_le_bytes();
+    Pubkey::find_program_address(&vault_seeds(group, index, &index_bytes), program_id).0
+}
+
+/// Convert an instruction to be proposed. `vaults` are indices of the group vaults that sign it.
+pub fn proposed_instruction(instruction: Instruction, vaults: Vec<u16>) -> ProposedInstruction {
+    ProposedInstruction {
+        program_id: instruction.program_id,
+        accounts: instruction
+            .accounts
+            .into_iter()
+            .map(|meta| ProposedAccountMeta {
+                pubkey: meta.pubkey,
+                is_signer: meta.is_signer,
+                is_writable: meta.is_writable,
+            })
+            .collect(),
+        data: instruction.data,
+        vaults,
+    }
+}
+
+/// Create [InitInstruction]. Group account key is derived from `group_data`.
+pub fn init(
+    program_id: &Pubkey,
+    initializer: &Pubkey,
+    group_data: GroupData,
+    lamports: u64,
+    space: u64,
+    protected_account_config: Option<ProtectedAccountConfig>,
+) -> Instruction {
+    let group = group_address(program_id, &group_data);
+    let mut accounts = vec![
+        AccountMeta::new(*initializer, true),
+        AccountMeta::new(group, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    if protected_account_config.is_some() {
+        accounts.push(AccountMeta::new(
+            protected_address(program_id, &group),
+            false,
+        ));
+    }
+
+    let command = MultiSigInstruction::Init(InitInstruction {
+        group_data,
+        lamports,
+        space,
+        protected_account_config,
+    });
+    Instruction::new_with_borsh(*program_id, &command, accounts)
+}
+
+/// Create [ProposeInstruction]. Proposal account key is the [proposal_address] of `index`,
+/// which has to be the current `proposal_count` of the group.
+#[allow(clippy::too_many_arguments)]
+pub fn propose(
+    program_id: &Pubkey,
+    proposer: &Pubkey,
+    group: &Pubkey,
+    index: u64,
+    instructions: Vec<ProposedInstruction>,
+    lamports: u64,
+    salt: u64,
+    expiry: Option<ProposalExpiry>,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(*proposer, true),
+        AccountMeta::new(*group, false),
+        AccountMeta::new(proposal_address(program_id, group, index), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+
+    let command = MultiSigInstruction::Propose(ProposeInstruction {
+        instructions,
+        lamports,
+        salt,
+        expiry,
+    });
+    Instruction::new_with_borsh(*program_id, &command, accounts)
+}
+
+/// Create [CreateDraftInstruction]. Proposal account key is the [proposal_address] of `index`,
+/// which has to be the current `proposal_count` of the group.
+#[allow(clippy::too_many_arguments)]
+pub fn create_draft(
+    program_id: &Pubkey,
+    proposer: &Pubkey,
+    group: &Pubkey,
+    index: u64,
+    lamports: u64,
+    space: u64,
+    salt: u64,
+    expiry: Option<ProposalExpiry>,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(*proposer, true),
+        AccountMeta::new(*group, false),
+        AccountMeta::new(proposal_address(program_id, group, index), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+
+    let command = MultiSigInstruction::CreateDraft(CreateDraftInstruction {
+        lamports,
+        space,
+        salt,
+        expiry,
+    });
+    Instruction::new_with_borsh(*program_id, &command, accounts)
+}
+
+/// Create [AppendToDraftInstruction].
+pub fn append_to_draft(
+    program_id: &Pubkey,
+    author: &Pubkey,
+    proposal: &Pubkey,
+    instructions: Vec<ProposedInstruction>,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(*author, true),
+        AccountMeta::new(*proposal, false),
+    ];
+
+    let command = MultiSigInstruction::AppendToDraft(AppendToDraftInstruction { instructions });
+    Instruction::new_with_borsh(*program_id, &command, accounts)
+}
+
+/// Create [FinalizeDraftInstruction].
+pub fn finalize_draft(
+    program_id: &Pubkey,
+    author: &Pubkey,
+    group: &Pubkey,
+    proposal: &Pubkey,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(*author, true),
+        AccountMeta::new_readonly(*group, false),
+        AccountMeta::new(*proposal, false),
+    ];
+
+    let command = MultiSigInstruction::FinalizeDraft(FinalizeDraftInstruction {});
+    Instruction::new_with_borsh(*program_id, &command, accounts)
+}
+
+/// Create [ApproveInstruction].
+pub fn approve(
+    program_id: &Pubkey,
+    approver: &Pubkey,
+    group: &Pubkey,
+    proposal: &Pubkey,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(*approver, true),
+        AccountMeta::new(*group, false),
+        AccountMeta::new(*proposal, false),
+    ];
+
+    let command = MultiSigInstruction::Approve(ApproveInstruction {});
+    Instruction::new_with_borsh(*program_id, &command, accounts)
+}
+
+/// Create a proposal instruction for `member_group` to approve a proposal of `group`,
+/// which has the protected account of `member_group` as a member.
+pub fn approve_as_group(
+    program_id: &Pubkey,
+    member_group: &Pubkey,
+    group: &Pubkey,
+    proposal: &Pubkey,
+) -> ProposedInstruction {
+    let approver = protected_address(program_id, member_group);
+    proposed_instruction(approve(program_id, &approver, group, proposal), vec![0])
+}
+
+/// Create [ApproveWithSignaturesInstruction] preceded by the Ed25519 program instruction
+/// checking `signatures` of members over the [ApprovalMessage] of the proposal.
+pub fn approve_with_signatures(
+    program_id: &Pubkey,
+    proposal: &Pubkey,
+    config: &ProposalConfig,
+    approval_nonce: u64,
+    signatures: &[(Pubkey, [u8; 64])],
+) -> Vec<Instruction> {
+    let message = ApprovalMessage::new(program_id, proposal, config, approval_nonce)
+        .and_then(|message| message.to_bytes())
+        .unwrap();
+    let accounts = vec![
+        AccountMeta::new_readonly(config.group, false),
+        AccountMeta::new(*proposal, false),
+        AccountMeta::new_readonly(sysvar::instructions::id(), false),
+    ];
+
+    let command = MultiSigInstruction::ApproveWithSignatures(ApproveWithSignaturesInstruction {});
+    vec![
+        ed25519::verify(signatures, &message),
+        Instruction::new_with_borsh(*program_id, &command, accounts),
+    ]
+}
+
+/// Create [RevokeApprovalInstruction].
+pub fn revoke_approval(
+    program_id: &Pubkey,
+    approver: &Pubkey,
+    group: &Pubkey,
+    proposal: &Pubkey,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(*approver, true),
+        AccountMeta::new_readonly(*group, false),
+        AccountMeta::new(*proposal, false),
+    ];
+
+    let command = MultiSigInstruction::RevokeApproval(RevokeApprovalInstruction {});
+    Instruction::new_with_borsh(*program_id, &command, accounts)
+}
+
+/// Create [RejectInstruction]. With `receipt`, a receipt account records the rejection if it
+/// closes the proposal.
+pub fn reject(
+    program_id: &Pubkey,
+    rejecter: &Pubkey,
+    group: &Pubkey,
+    proposal: &Pubkey,
+    author: &Pubkey,
+    receipt: bool,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*rejecter, true),
+        AccountMeta::new_readonly(*group, false),
+        AccountMeta::new(*proposal, false),
+        AccountMeta::new(*author, false),
+    ];
+    if receipt {
+        accounts.push(AccountMeta::new(
+            receipt_address(program_id, proposal),
+            false,
+        ));
+        accounts.push(AccountMeta::new_readonly(system_program::id(), false));
+    }
+
+    let command = MultiSigInstruction::Reject(RejectInstruction {});
+    Instruction::new_with_borsh(*program_id, &command, accounts)
+}
+
+/// Create [ExecuteInstruction] for the proposal stored at `proposal` with `config`.
+pub fn execute(
+    program_id: &Pubkey,
+    executor: &Pubkey,
+    proposal: &Pubkey,
+    config: &ProposalConfig,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(*executor, true),
+        AccountMeta::new(config.group, false),
+        AccountMeta::new(*proposal, false),
+        AccountMeta::new(protected_address(program_id, &config.group), false),
+    ];
+    accounts.extend(proposed_accounts(
+        program_id,
+        &config.group,
+        &config.instructions,
+    ));
+
+    let command = MultiSigInstruction::Execute(ExecuteInstruction {});
+    Instruction::new_with_borsh(*program_id, &command, accounts)
+}
+
+/// Create [ExecuteWithReceiptInstruction]. The executor funds the receipt with `lamports`.
+pub fn execute_with_receipt(
+    program_id: &Pubkey,
+    executor: &Pubkey,
+    proposal: &Pubkey,
+    config: &ProposalConfig,
+    lamports: u64,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(*executor, true),
+        AccountMeta::new(config.group, false),
+        AccountMeta::new(*proposal, false),
+        AccountMeta::new(protected_address(program_id, &config.group), false),
+        AccountMeta::new(receipt_address(program_id, proposal), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    accounts.extend(proposed_accounts(
+        program_id,
+        &config.group,
+        &config.instructions,
+    ));
+
+    let command =
+        MultiSigInstruction::ExecuteWithReceipt(ExecuteWithReceiptInstruction { lamports });
+    Instruction::new_with_borsh(*program_id, &command, accounts)
+}
+
+/// Create [CloseProposalInstruction]. With `receipt`, a receipt account records the
+/// cancellation or expiry.
+pub fn close_proposal(
+    program_id: &Pubkey,
+    closer: &Pubkey,
+    proposal: &Pubkey,
+    destination: &Pubkey,
+    receipt: bool,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(*closer, true),
+        AccountMeta::new(*proposal, false),
+        AccountMeta::new(*destination, false),
+    ];
+    if receipt {
+        accounts.push(AccountMeta::new(
+            receipt_address(program_id, proposal),
+            false,
+        ));
+        accounts.push(AccountMeta::new_readonly(system_program::id(), false));
+    }
+
+    let command = MultiSigInstruction::CloseProposal(CloseProposalInstruction {});
+    Instruction::new_with_borsh(*program_id, &command, accounts)
+}
+
+/// Create [UpdateGroupInstruction]. Has to be proposed, as it is signed by the protected account.
+pub fn update_group(program_id: &Pubkey, group: &Pubkey, group_data: GroupData) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(protected_address(program_id, group), true),
+        AccountMeta::new(*group, false),
+    ];
+
+    let command = MultiSigInstruction::UpdateGroup(UpdateGroupInstruction { group_data });
+    Instruction::new_with_borsh(*program_id, &command, accounts)
+}
+
+/// Create [CreateVaultInstruction]. Has to be proposed, as it is signed by the protected account.
+/// `funder` must sign too, it can be the protected account.
+pub fn create_vault(
+    program_id: &Pubkey,
+    group: &Pubkey,
+    funder: &Pubkey,
+    index: u16,
+    config: ProtectedAccountConfig,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(protected_address(program_id, group), true),
+        AccountMeta::new(*group, false),
+        AccountMeta::new(*funder, true),
+        AccountMeta::new(vault_address(program_id, group, index), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+
+    let command = MultiSigInstruction::CreateVault(CreateVaultInstruction { index, config });
+    Instruction::new_with_borsh(*program_id, &command, accounts)
+}
+
+/// Create [MigrateInstruction] for a group or proposal account. `funder` pays for growing the
+/// account if the current layout doesn't fit.
+pub fn migrate(program_id: &Pubkey, account: &Pubkey, funder: Option<&Pubkey>) -> Instruction {
+    let mut accounts = vec![AccountMeta::new(*account, false)];
+    if let Some(funder) = funder {
+        accounts.push(AccountMeta::new(*funder, true));
+        accounts.push(AccountMeta::new_readonly(system_program::id(), false));
+    }
+
+    let command = MultiSigInstruction::Migrate(MigrateInstruction {});
+    Instruction::new_with_borsh(*program_id, &command, accounts)
+}
+
+/// Create [CreateSpendingLimitInstruction]. Has to be proposed, as it is signed by the protected
+/// account. `funder` must sign too, it can be the protected account.
+pub fn create_spending_limit(
+    program_id: &Pubkey,
+    group: &Pubkey,
+    funder: &Pubkey,
+    index: u16,
+    config: SpendingLimitConfig,
+    lamports: u64,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(protected_address(program_id, group), true),
+        AccountMeta::new_readonly(*group, false),
+        AccountMeta::new(*funder, true),
+        AccountMeta::new(spending_limit_address(program_id, group, index), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+
+    let command = MultiSigInstruction::CreateSpendingLimit(CreateSpendingLimitInstruction {
+        index,
+        config,
+        lamports,
+    });
+    Instruction::new_with_borsh(*program_id, &command, accounts)
+}
+
+/// Create [RemoveSpendingLimitInstruction]. Has to be proposed, as it is signed by the protected
+/// account.
+pub fn remove_spending_limit(
+    program_id: &Pubkey,
+    group: &Pubkey,
+    index: u16,
+    destination: &Pubkey,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(protected_address(program_id, group), true),
+        AccountMeta::new_readonly(*group, false),
+        AccountMeta::new(spending_limit_address(program_id, group, index), false),
+        AccountMeta::new(*destination, false),
+    ];
+
+    let command = MultiSigInstruction::RemoveSpendingLimit(RemoveSpendingLimitInstruction {});
+    Instruction::new_with_borsh(*program_id, &command, accounts)
+}
+
+/// Create [SpendWithinLimitInstruction] for the limit with the given index and `config`.
+/// `source` is the vault token account for token limits and is ignored for lamport limits.
+#[allow(clippy::too_many_arguments)]
+pub fn spend_within_limit(
+    program_id: &Pubkey,
+    member: &Pubkey,
+    group: &Pubkey,
+    index: u16,
+    config: &SpendingLimitConfig,
+    source: &Pubkey,
+    destination: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*member, true),
+        AccountMeta::new_readonly(*group, false),
+        AccountMeta::new(spending_limit_address(program_id, group, index), false),
+        AccountMeta::new(vault_address(program_id, group, config.vault), false),
+        AccountMeta::new(*destination, false),
+    ];
+    match config.mint {
+        None => accounts.push(AccountMeta::new_readonly(system_program::id(), false)),
+        Some(_) => {
+            accounts.push(AccountMeta::new_readonly(token::id(), false));
+            accounts.push(AccountMeta::new(*source, false));
+        }
+    }
+
+    let command = MultiSigInstruction::SpendWithinLimit(SpendWithinLimitInstruction { amount });
+    Instruction::new_with_borsh(*program_id, &command, accounts)
+}
+
+/// Accounts the proposed instructions need to be invoked. Vaults are signed by the program, so
+/// they are passed as non-signers.
+fn proposed_accounts(
+    program_id: &Pubkey,
+    group: &Pubkey,
+    instructions: &[ProposedInstruction],
+) -> Vec<AccountMeta> {
+    let mut accounts = Vec::new();
+    for instruction in instructions {
+        let vaults: Vec<Pubkey> = instruction
+            .vaults
+            .iter()
+            .map(|index| vault_address(program_id, group, *index))
+            .collect();
+        accounts.push(AccountMeta::new_readonly(instruction.program_id, false));
+        accounts.extend(instruction.accounts.iter().map(|account| {
+            let is_signer = account.is_signer && !vaults.contains(&account.pubkey);
+            if account.is_writable {
+                AccountMeta::new(account.pubkey, is_signer)
+            } else {
+                AccountMeta::new_readonly(account.pubkey, is_signer)
+            }
+        }));
+    }
+    accounts
+}
@@ -0,0 +1,81 @@
+//! Minimal Ed25519 signature verification program interface, just enough to approve proposals
+//! with signatures made off-chain.
+
+use solana_program::{instruction::Instruction, pubkey::Pubkey};
+
+solana_program::declare_id!("Ed25519SigVerify111111111111111111111111111");
+
+const PUBKEY_LEN: usize = 32;
+const SIGNATURE_LEN: usize = 64;
+/// Signature count and a padding byte precede signature offsets.
+const OFFSETS_START: usize = 2;
+/// Seven `u16` offsets and instruction indices per signature.
+const OFFSETS_LEN: usize = 14;
+/// Instruction index meaning the verify instruction itself.
+const THIS_INSTRUCTION: u16 = u16::MAX;
+
+/// Verify instruction checking that each of `signatures` is made by its key over `message`.
+pub fn verify(signatures: &[(Pubkey, [u8; SIGNATURE_LEN])], message: &[u8]) -> Instruction {
+    let data_start = OFFSETS_START + OFFSETS_LEN * signatures.len();
+    let message_offset = data_start + (PUBKEY_LEN + SIGNATURE_LEN) * signatures.len();
+
+    let mut data = vec![signatures.len() as u8, 0];
+    for i in 0..signatures.len() {
+        let public_key_offset = data_start + (PUBKEY_LEN + SIGNATURE_LEN) * i;
+        let signature_offset = public_key_offset + PUBKEY_LEN;
+        for value in [
+            signature_offset as u16,
+            THIS_INSTRUCTION,
+            public_key_offset as u16,
+            THIS_INSTRUCTION,
+            message_offset as u16,
+            message.len() as u16,
+            THIS_INSTRUCTION,
+        ] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+    for (key, signature) in signatures {
+        data.extend_from_slice(key.as_ref());
+        data.extend_from_slice(signature);
+    }
+    data.extend_from_slice(message);
+
+    Instruction {
+        program_id: id(),
+        accounts: vec![],
+        data,
+    }
+}
+
+/// Keys whose signatures over `message` are checked by the verify instruction at `index`
+/// of the transaction. None if it checks other messages or refers to other instructions.
+pub fn signers(instruction: &Instruction, index: u16, message: &[u8]) -> Option<Vec<Pubkey>> {
+    if instruction.program_id != id() {
+        return None;
+    }
+    let data = &instruction.data;
+    let count = *data.first()? as usize;
+    let slice = |offset: u16, len: usize| data.get(offset as usize..offset as usize + len);
+
+    let mut keys = Vec::with_capacity(count);
+    for i in 0..count {
+        let start = OFFSETS_START + OFFSETS_LEN * i;
+        let offsets = data.get(start..start + OFFSETS_LEN)?;
+        let value = |n: usize| u16::from_le_bytes([offsets[2 * n], offsets[2 * n + 1]]);
+        // Signed data must be part of the verify instruction itself.
+        if [value(1), value(3), value(6)]
+            .iter()
+            .any(|&instruction_index| {
+                instruction_index != THIS_INSTRUCTION && instruction_index != index
+            })
+        {
+            return None;
+        }
+        if slice(value(4), value(5) as usize)? != message {
+            return None;
+        }
+        keys.push(Pubkey::new(slice(value(2), PUBKEY_LEN)?));
+    }
+    Some(keys)
+}
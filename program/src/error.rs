@@ -1,14 +1,16 @@
+use std::io;
+
 use solana_program::program_error::ProgramError;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum Error {
     #[error("invalid instruction data: {0}")]
-    InvalidInstructionData(#[source] std::io::Error),
+    InvalidInstructionData(#[source] io::Error),
     #[error("invalid account data: {0}")]
-    AccountDataDeserialize(#[source] std::io::Error),
+    AccountDataDeserialize(#[source] io::Error),
     #[error("serialization failed: {0}")]
-    Serialize(#[source] std::io::Error),
+    Serialize(#[source] io::Error),
     #[error("{0}")]
     Program(#[from] ProgramError),
     #[error("threshold can't be zero")]
@@ -35,27 +37,157 @@ pub enum Error {
     InvalidAccountType,
     #[error("empty account data")]
     EmptyAccountData,
+    #[error("proposal is expired")]
+    ProposalExpired,
+    #[error("invalid destination account key")]
+    InvalidDestinationAccountKey,
+    #[error("proposal has not reached the threshold")]
+    ProposalNotApproved,
+    #[error("execution delay has not passed yet")]
+    ExecutionDelayNotElapsed,
+    #[error("execution delay doesn't fit a timestamp")]
+    InvalidExecutionDelay,
+    #[error("proposal is already executed")]
+    ProposalAlreadyExecuted,
+    #[error("you don't participate in this proposal")]
+    NotParticipating,
+    #[error("member joined the group after the proposal was created")]
+    InvalidMemberIndex,
+    #[error("vault with this index already exists")]
+    VaultAlreadyExists,
+    #[error("proposal is a draft")]
+    ProposalIsDraft,
+    #[error("proposal is not a draft")]
+    ProposalIsNotDraft,
+    #[error("account layout version is not supported")]
+    UnsupportedAccountVersion,
+    #[error("account has to be migrated to the current layout first")]
+    AccountNotMigrated,
+    #[error("invalid spending limit account key")]
+    InvalidSpendingLimitAccountKey,
+    #[error("spending limit period must be positive")]
+    InvalidSpendingLimitPeriod,
+    #[error("amount exceeds the spending limit for the period")]
+    SpendingLimitExceeded,
+    #[error("token account doesn't belong to the vault or holds another mint")]
+    InvalidTokenAccount,
+    #[error("group policy doesn't allow invoking the program")]
+    ProgramNotAllowed,
+    #[error("approvals must follow an Ed25519 program instruction signing the approval message")]
+    InvalidSignatureInstruction,
+    #[error("proposal was rejected, cancelled or expired")]
+    ProposalClosed,
+    #[error("invalid receipt account key")]
+    InvalidReceiptAccountKey,
+    #[error("proposal was created for an older group configuration")]
+    StaleProposal,
+}
+
+impl Error {
+    /// Code of the [ProgramError::Custom] the error is returned as. Codes are part of the program
+    /// interface: new variants get new codes, existing codes never change. [Error::Serialize] keeps
+    /// the code it was returned with before every error got one.
+    /// `None` for [Error::Program], which is returned as is.
+    pub fn custom_code(&self) -> Option<u32> {
+        let code = match self {
+            Error::Program(_) => return None,
+            Error::InvalidInstructionData(_) => 0,
+            Error::Serialize(_) => 1,
+            Error::AccountDataDeserialize(_) => 2,
+            Error::ZeroThreshold => 3,
+            Error::TooManyMembers => 4,
+            Error::NoMembers => 5,
+            Error::ZeroWeight => 6,
+            Error::UnreachableThreshold => 7,
+            Error::InvalidGroupAccountKey => 8,
+            Error::InvalidProposalAccountKey => 9,
+            Error::InvalidProtectedAccountKey => 10,
+            Error::Unauthorized => 11,
+            Error::AlreadyParticipate => 12,
+            Error::InvalidAccountType => 13,
+            Error::EmptyAccountData => 14,
+            Error::ProposalExpired => 15,
+            Error::InvalidDestinationAccountKey => 16,
+            Error::ProposalNotApproved => 17,
+            Error::ExecutionDelayNotElapsed => 18,
+            Error::InvalidExecutionDelay => 19,
+            Error::ProposalAlreadyExecuted => 20,
+            Error::NotParticipating => 21,
+            Error::InvalidMemberIndex => 22,
+            Error::VaultAlreadyExists => 23,
+            Error::ProposalIsDraft => 24,
+            Error::ProposalIsNotDraft => 25,
+            Error::UnsupportedAccountVersion => 26,
+            Error::AccountNotMigrated => 27,
+            Error::InvalidSpendingLimitAccountKey => 28,
+            Error::InvalidSpendingLimitPeriod => 29,
+            Error::SpendingLimitExceeded => 30,
+            Error::InvalidTokenAccount => 31,
+            Error::ProgramNotAllowed => 32,
+            Error::InvalidSignatureInstruction => 33,
+            Error::ProposalClosed => 34,
+            Error::InvalidReceiptAccountKey => 35,
+            Error::StaleProposal => 36,
+        };
+        Some(code)
+    }
+
+    /// Decode an error returned by the program as [ProgramError::Custom].
+    /// Underlying IO errors are not transferred, so they are replaced with a generic one.
+    pub fn from_custom_code(code: u32) -> Option<Self> {
+        let error = match code {
+            0 => Error::InvalidInstructionData(io::ErrorKind::InvalidData.into()),
+            1 => Error::Serialize(io::ErrorKind::Other.into()),
+            2 => Error::AccountDataDeserialize(io::ErrorKind::InvalidData.into()),
+            3 => Error::ZeroThreshold,
+            4 => Error::TooManyMembers,
+            5 => Error::NoMembers,
+            6 => Error::ZeroWeight,
+            7 => Error::UnreachableThreshold,
+            8 => Error::InvalidGroupAccountKey,
+            9 => Error::InvalidProposalAccountKey,
+            10 => Error::InvalidProtectedAccountKey,
+            11 => Error::Unauthorized,
+            12 => Error::AlreadyParticipate,
+            13 => Error::InvalidAccountType,
+            14 => Error::EmptyAccountData,
+            15 => Error::ProposalExpired,
+            16 => Error::InvalidDestinationAccountKey,
+            17 => Error::ProposalNotApproved,
+            18 => Error::ExecutionDelayNotElapsed,
+            19 => Error::InvalidExecutionDelay,
+            20 => Error::ProposalAlreadyExecuted,
+            21 => Error::NotParticipating,
+            22 => Error::InvalidMemberIndex,
+            23 => Error::VaultAlreadyExists,
+            24 => Error::ProposalIsDraft,
+            25 => Error::ProposalIsNotDraft,
+            26 => Error::UnsupportedAccountVersion,
+            27 => Error::AccountNotMigrated,
+            28 => Error::InvalidSpendingLimitAccountKey,
+            29 => Error::InvalidSpendingLimitPeriod,
+            30 => Error::SpendingLimitExceeded,
+            31 => Error::InvalidTokenAccount,
+            32 => Error::ProgramNotAllowed,
+            33 => Error::InvalidSignatureInstruction,
+            34 => Error::ProposalClosed,
+            35 => Error::InvalidReceiptAccountKey,
+            36 => Error::StaleProposal,
+            _ => return None,
+        };
+        Some(error)
+    }
 }
 
 impl From<Error> for ProgramError {
     fn from(error: Error) -> Self {
         match error {
             Error::Program(error) => error,
-            Error::InvalidInstructionData(_) => ProgramError::InvalidInstructionData,
-            Error::AccountDataDeserialize(_)
-            | Error::InvalidAccountType
-            | Error::EmptyAccountData => ProgramError::InvalidAccountData,
-            Error::Serialize(_) => ProgramError::Custom(1),
-            Error::ZeroThreshold
-            | Error::TooManyMembers
-            | Error::NoMembers
-            | Error::ZeroWeight
-            | Error::UnreachableThreshold
-            | Error::InvalidGroupAccountKey
-            | Error::InvalidProposalAccountKey
-            | Error::InvalidProtectedAccountKey
-            | Error::Unauthorized
-            | Error::AlreadyParticipate => ProgramError::InvalidArgument,
+            error => ProgramError::Custom(
+                error
+                    .custom_code()
+                    .expect("only program errors have no custom code"),
+            ),
         }
     }
 }
@@ -0,0 +1,171 @@
+//! Events logged by the program on state transitions, so indexers don't have to diff accounts.
+//!
+//! Each event is logged as `multisig event: <base64>`, where the encoded record is the event
+//! layout version followed by the Borsh-encoded [Event].
+
+use std::io;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{msg, pubkey::Pubkey};
+
+use crate::error::Error;
+use crate::state::ProposalStatus;
+
+/// Layout version of logged events.
+pub const EVENT_VERSION: u8 = 1;
+
+const LOG_PREFIX: &str = "multisig event: ";
+/// Prefix the runtime puts before lines logged by programs.
+const PROGRAM_LOG_PREFIX: &str = "Program log: ";
+
+#[derive(Debug, Clone, PartialEq, BorshSerialize, BorshDeserialize)]
+pub enum Event {
+    GroupCreated {
+        group: Pubkey,
+        threshold: u32,
+        member_count: u32,
+    },
+    GroupUpdated {
+        group: Pubkey,
+        threshold: u32,
+        member_count: u32,
+    },
+    VaultCreated {
+        group: Pubkey,
+        index: u16,
+        vault: Pubkey,
+    },
+    /// Proposal or draft was created. Proposer's approval follows as a separate event.
+    Proposed {
+        group: Pubkey,
+        proposal: Pubkey,
+        author: Pubkey,
+    },
+    Approved {
+        group: Pubkey,
+        proposal: Pubkey,
+        member: Pubkey,
+        weight: u32,
+        /// Total weight of the proposal approvals, including this one.
+        current_weight: u32,
+    },
+    ApprovalRevoked {
+        group: Pubkey,
+        proposal: Pubkey,
+        member: Pubkey,
+        weight: u32,
+        current_weight: u32,
+    },
+    Rejected {
+        group: Pubkey,
+        proposal: Pubkey,
+        member: Pubkey,
+        weight: u32,
+        rejected_weight: u32,
+    },
+    Executed {
+        group: Pubkey,
+        proposal: Pubkey,
+    },
+    /// Proposal account was closed and its lamports were transferred to `destination`.
+    Closed {
+        proposal: Pubkey,
+        destination: Pubkey,
+        lamports: u64,
+    },
+    SpendingLimitCreated {
+        group: Pubkey,
+        index: u16,
+        limit: Pubkey,
+    },
+    SpendingLimitRemoved {
+        group: Pubkey,
+        limit: Pubkey,
+    },
+    SpentWithinLimit {
+        group: Pubkey,
+        limit: Pubkey,
+        member: Pubkey,
+        amount: u64,
+        /// Total amount spent in the current period, including this one.
+        spent: u64,
+    },
+    /// Proposal was rejected, cancelled by its author or expired. Its account is closed next.
+    Ended {
+        group: Pubkey,
+        proposal: Pubkey,
+        status: ProposalStatus,
+    },
+    ReceiptCreated {
+        group: Pubkey,
+        proposal: Pubkey,
+        receipt: Pubkey,
+    },
+}
+
+impl Event {
+    /// Log the event.
+    pub fn emit(&self) -> Result<(), Error> {
+        msg!("{}", self.to_log()?);
+        Ok(())
+    }
+
+    /// Log line carrying the event, as passed to `msg!`.
+    pub fn to_log(&self) -> Result<String, Error> {
+        let mut record = vec![EVENT_VERSION];
+        self.serialize(&mut record).map_err(Error::Serialize)?;
+        Ok(format!("{}{}", LOG_PREFIX, base64::encode(record)))
+    }
+
+    /// Decode an event from a transaction log line. `None` if the line doesn't carry an event.
+    pub fn from_log(line: &str) -> io::Result<Option<Self>> {
+        let line = line.strip_prefix(PROGRAM_LOG_PREFIX).unwrap_or(line);
+        let encoded = match line.strip_prefix(LOG_PREFIX) {
+            Some(encoded) => encoded,
+            None => return Ok(None),
+        };
+        let record = base64::decode(encoded)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        match record.split_first() {
+            Some((&EVENT_VERSION, mut data)) => Ok(Some(Self::deserialize(&mut data)?)),
+            Some((version, _)) => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported event version {}", version),
+            )),
+            None => Err(io::ErrorKind::UnexpectedEof.into()),
+        }
+    }
+
+    /// Decode events logged by `program_id` from transaction logs, in order. Lines logged by other
+    /// programs are skipped, so they can't forge events.
+    pub fn from_logs<'a>(
+        program_id: &Pubkey,
+        logs: impl IntoIterator<Item = &'a str>,
+    ) -> io::Result<Vec<Self>> {
+        let program_id = program_id.to_string();
+        let mut invoked = Vec::new();
+        let mut events = Vec::new();
+        for line in logs {
+            if !line.starts_with(PROGRAM_LOG_PREFIX) {
+                // Invocation status lines look like `Program <id> invoke [<depth>]`,
+                // `Program <id> success` or `Program <id> failed: <error>`.
+                let mut words = line.split_whitespace().skip(1);
+                match (words.next(), words.next()) {
+                    (Some(id), Some("invoke")) => invoked.push(id.to_owned()),
+                    (Some(_), Some("success")) | (Some(_), Some("failed:")) => {
+                        invoked.pop();
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+            if invoked.last() != Some(&program_id) {
+                continue;
+            }
+            if let Some(event) = Self::from_log(line)? {
+                events.push(event);
+            }
+        }
+        Ok(events)
+    }
+}
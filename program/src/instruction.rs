@@ -1,7 +1,7 @@
 use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::pubkey::Pubkey;
 
-use crate::state::{GroupData, ProposedInstruction};
+use crate::state::{GroupData, ProposalExpiry, ProposedInstruction, SpendingLimitConfig};
 
 #[derive(Debug, BorshDeserialize, BorshSerialize)]
 pub struct ProtectedAccountConfig {
@@ -26,20 +26,22 @@ pub struct InitInstruction {
     pub group_data: GroupData,
     /// Amount of lamports to fund the new group account.
     pub lamports: u64,
+    /// Amount of space to allocate for group account. Raised to the size of `group_data` if
+    /// smaller. Extra space lets later group updates add members and vaults be created.
+    pub space: u64,
     /// Optional config to create protected account with.
     pub protected_account_config: Option<ProtectedAccountConfig>,
 }
 
-/// Propose instruction to be executed by the group.
+/// Propose instruction to be executed by the group. The proposal account is always created, even
+/// if proposer's weight alone reaches the threshold, and is executed with [ExecuteInstruction].
 ///
 /// # Account references
 ///   0. `[SIGNER, WRITE]` Proposer account. Must be a member of the group.
-///   1. `[WRITE]` Group account. TODO: Remove writable
-///   2. `[WRITE]` Proposal account. Must not exist. Key must be a PDA seeded by proposed instruction
-///                and invoked program_id (See [ProposalConfig](crate::state::ProposalConfig)).
+///   1. `[WRITE]` Group account. Counts the created proposals.
+///   2. `[WRITE]` Proposal account. Must not exist. Key must be a PDA seeded by group account and
+///      the group's current proposal count (See [proposal_address](crate::client::proposal_address)).
 ///   3. `[]` System program account.
-///   4. `[]` Proposed instruction program account.
-///   5. ..5+N `[]` N accounts needed for proposed instructions to succeed.
 #[derive(Debug, BorshDeserialize, BorshSerialize)]
 pub struct ProposeInstruction {
     /// Instruction to be proposed.
@@ -47,33 +49,257 @@ pub struct ProposeInstruction {
     /// Amount of lamports to fund the new proposal account.
     pub lamports: u64,
     /// A salt that will make this proposal unique.
-    pub salt: u64
+    pub salt: u64,
+    /// Optional slot or timestamp after which the proposal can't be approved anymore.
+    pub expiry: Option<ProposalExpiry>,
 }
 
-/// Approve already proposed instruction.
+/// Approve already proposed instruction. Only records the vote, proposal that reached the threshold
+/// is executed with [ExecuteInstruction].
+///
+/// A member can be another group's protected account, which approves by executing a proposal of
+/// that group (see [approve_as_group](crate::client::approve_as_group)).
 ///
 /// # Account references
 ///   0. `[SIGNER, WRITE]` Approver account. Must be a member of the group.
 ///   1. `[WRITE]` Group account. TODO: Remove writable
 ///   2. `[WRITE]` Proposal account that holds instruction to be approved.
-///   3. `[WRITE]` Protected group account. Used to transfer lamports back to if the proposal is closed.
+#[derive(Debug, BorshDeserialize, BorshSerialize)]
+pub struct ApproveInstruction {} // TODO?: is this unit?
+
+/// Revoke an approval given earlier. Proposal that drops below the threshold has to be approved
+/// again, restarting the group's execution delay. Executed proposal can't be revoked.
+///
+/// # Account references
+///   0. `[SIGNER]` Approver account. Must be a member of the group that approved the proposal.
+///   1. `[]` Group account.
+///   2. `[WRITE]` Proposal account.
+#[derive(Debug, BorshDeserialize, BorshSerialize)]
+pub struct RevokeApprovalInstruction {}
+
+/// Vote against a proposal. Once the rest of the group can't reach the threshold anymore,
+/// proposal is closed and its lamports are returned to the author.
+///
+/// # Account references
+///   0. `[SIGNER]` Rejecter account. Must be a member of the group.
+///   1. `[]` Group account.
+///   2. `[WRITE]` Proposal account.
+///   3. `[WRITE]` Proposal author account. Will receive lamports if the proposal is closed.
+///   4. `[WRITE]` Optional receipt account recording the rejection if the proposal is closed.
+///      Key must be a PDA seeded by the proposal account key. Funded from the proposal lamports.
+///   5. `[]` System program account. Needed with the receipt account.
+#[derive(Debug, BorshDeserialize, BorshSerialize)]
+pub struct RejectInstruction {}
+
+/// Execute a proposal that reached the threshold once the group's execution delay has passed.
+/// Can be called by anyone.
+///
+/// # Account references
+///   0. `[SIGNER, WRITE]` Executor account.
+///   1. `[WRITE]` Group account.
+///   2. `[WRITE]` Proposal account that holds instruction to be executed.
+///   3. `[WRITE]` Protected group account. Used to transfer lamports back to as the proposal is closed.
 ///   4. `[]` Proposed instruction program account.
 ///   5. ..5+N `[]` N accounts needed for proposed instruction to succeed.
 #[derive(Debug, BorshDeserialize, BorshSerialize)]
-pub struct ApproveInstruction {} // TODO?: is this unit?
+pub struct ExecuteInstruction {}
 
 /// Closes a proposal and transfers its lamports to a group's protected address.
+/// Expired proposal can be closed by anyone, but its lamports go back to the author.
 /// # Account references
-///   0. `[SIGNER, WRITE]` Closer account. Must be a member of the group. Must be the same one who created the proposal.
+///   0. `[SIGNER, WRITE]` Closer account. Must be the same one who created the proposal, unless proposal is expired.
 ///   2. `[WRITE]` Proposal account that holds instruction to be approved.
 ///   3. `[WRITE]` Destination account. Will receive lamports that proposal account has.
+///      Must be the proposal author if closer is not.
+///   4. `[WRITE]` Optional receipt account recording the cancellation or expiry.
+///      Key must be a PDA seeded by the proposal account key. Funded from the proposal lamports.
+///   5. `[]` System program account. Needed with the receipt account.
 #[derive(Debug, BorshDeserialize, BorshSerialize)]
 pub struct CloseProposalInstruction {}
 
+/// Replace members and threshold of the group. Keeps group and protected account keys.
+/// Can only be invoked by the group itself, i.e. as an instruction of an approved proposal.
+/// Other open proposals of the group become stale: they can't be voted on or executed anymore,
+/// only closed.
+///
+/// # Account references
+///   0. `[SIGNER]` Protected group account.
+///   1. `[WRITE]` Group account to update. Must be large enough to hold new group data.
+#[derive(Debug, BorshDeserialize, BorshSerialize)]
+pub struct UpdateGroupInstruction {
+    /// New group configuration data.
+    pub group_data: GroupData,
+}
+
+/// Create an additional group vault. Vault 0 is the protected account created by [InitInstruction].
+/// Can only be invoked by the group itself, i.e. as an instruction of an approved proposal.
+/// Fails if the vault already exists.
+///
+/// # Account references
+///   0. `[SIGNER]` Protected group account.
+///   1. `[WRITE]` Group account. Stores the vault bump, so it needs 3 bytes of spare space.
+///   2. `[WRITE, SIGNER]` Funding account. Can be the protected group account.
+///   3. `[WRITE]` Vault account to create. Key must be a PDA seeded by group account and vault index.
+///   4. `[]` System program account.
+#[derive(Debug, BorshDeserialize, BorshSerialize)]
+pub struct CreateVaultInstruction {
+    /// Index of the vault.
+    pub index: u16,
+    /// Config to create vault account with.
+    pub config: ProtectedAccountConfig,
+}
+
+/// Create a draft proposal with no instructions, for proposals too large to fit into a transaction.
+/// Instructions are added with [AppendToDraftInstruction], then [FinalizeDraftInstruction]
+/// opens the proposal for votes.
+///
+/// # Account references
+///   0. `[SIGNER, WRITE]` Proposer account. Must be a member of the group.
+///   1. `[WRITE]` Group account. Counts the created proposals.
+///   2. `[WRITE]` Proposal account. Must not exist. Key must be a PDA seeded by group account and
+///      the group's current proposal count (See [proposal_address](crate::client::proposal_address)).
+///   3. `[]` System program account.
+#[derive(Debug, BorshDeserialize, BorshSerialize)]
+pub struct CreateDraftInstruction {
+    /// Amount of lamports to fund the new proposal account.
+    pub lamports: u64,
+    /// Amount of space to allocate for proposal account. Must be enough to hold all instructions.
+    pub space: u64,
+    /// A salt that will make this proposal unique.
+    pub salt: u64,
+    /// Optional slot or timestamp after which the proposal can't be approved anymore.
+    pub expiry: Option<ProposalExpiry>,
+}
+
+/// Add instructions to a draft proposal.
+///
+/// # Account references
+///   0. `[SIGNER]` Proposal author account.
+///   1. `[WRITE]` Draft proposal account.
+#[derive(Debug, BorshDeserialize, BorshSerialize)]
+pub struct AppendToDraftInstruction {
+    /// Instructions to add to the proposal.
+    pub instructions: Vec<ProposedInstruction>,
+}
+
+/// Finalize a draft proposal. Its instructions can't be changed anymore, the author's approval is
+/// recorded and other members can vote on it. Expired drafts can't be finalized.
+///
+/// # Account references
+///   0. `[SIGNER]` Proposal author account. Must be a member of the group.
+///   1. `[]` Group account.
+///   2. `[WRITE]` Draft proposal account.
+#[derive(Debug, BorshDeserialize, BorshSerialize)]
+pub struct FinalizeDraftInstruction {}
+
+/// Rewrite a group or proposal account in the current layout version, e.g. after a program
+/// upgrade changed the layout. Anyone can migrate an account, as the data itself doesn't change.
+/// Accounts created by the first program release have no version and are read in their original
+/// layout. Account too small for the current layout is grown by at most 10 KiB, and its rent
+/// exemption is topped up by the funding account.
+///
+/// # Account references
+///   0. `[WRITE]` Group, proposal, spending limit or receipt account.
+///   1. `[WRITE, SIGNER]` Optional funding account. Needed if the account needs more lamports.
+///   2. `[]` Optional system program account. Needed with the funding account.
+#[derive(Debug, BorshDeserialize, BorshSerialize)]
+pub struct MigrateInstruction {}
+
+/// Create a spending limit letting members spend from a vault without a proposal.
+/// Can only be invoked by the group itself, i.e. as an instruction of an approved proposal.
+/// The vault has to be created first.
+///
+/// # Account references
+///   0. `[SIGNER]` Protected group account.
+///   1. `[]` Group account.
+///   2. `[WRITE, SIGNER]` Funding account. Can be the protected group account.
+///   3. `[WRITE]` Spending limit account to create. Key must be a PDA seeded by group account and
+///      limit index.
+///   4. `[]` System program account.
+#[derive(Debug, BorshDeserialize, BorshSerialize)]
+pub struct CreateSpendingLimitInstruction {
+    /// Index of the limit. Any unused index can be taken.
+    pub index: u16,
+    pub config: SpendingLimitConfig,
+    /// Amount of lamports to fund the spending limit account.
+    pub lamports: u64,
+}
+
+/// Remove a spending limit and transfer its lamports to a destination account.
+/// Can only be invoked by the group itself, i.e. as an instruction of an approved proposal.
+///
+/// # Account references
+///   0. `[SIGNER]` Protected group account.
+///   1. `[]` Group account.
+///   2. `[WRITE]` Spending limit account.
+///   3. `[WRITE]` Destination account.
+#[derive(Debug, BorshDeserialize, BorshSerialize)]
+pub struct RemoveSpendingLimitInstruction {}
+
+/// Spend from a group vault within a spending limit, signed by the vault.
+///
+/// # Account references
+///   0. `[SIGNER]` Member account. Must be a group member allowed by the limit.
+///   1. `[]` Group account.
+///   2. `[WRITE]` Spending limit account.
+///   3. `[WRITE]` Vault account the limit spends from.
+///   4. `[WRITE]` Destination account. A token account of the limit mint for token limits.
+///   5. `[]` System program account for lamport limits, token program account for token limits.
+///   6. `[WRITE]` Token account owned by the vault, for token limits only.
+#[derive(Debug, BorshDeserialize, BorshSerialize)]
+pub struct SpendWithinLimitInstruction {
+    /// Amount of lamports or tokens to transfer.
+    pub amount: u64,
+}
+
+/// Approve a proposal on behalf of members who signed its
+/// [ApprovalMessage](crate::state::ApprovalMessage) off-chain. Signatures are
+/// checked by an Ed25519 program instruction right before this one in the transaction.
+/// Can be called by anyone.
+///
+/// # Account references
+///   0. `[]` Group account.
+///   1. `[WRITE]` Proposal account.
+///   2. `[]` Instructions sysvar account.
+#[derive(Debug, BorshDeserialize, BorshSerialize)]
+pub struct ApproveWithSignaturesInstruction {}
+
+/// Execute a proposal like [ExecuteInstruction] and keep a receipt account recording
+/// the execution, as the proposal account itself is closed.
+///
+/// # Account references
+///   0. `[SIGNER, WRITE]` Executor account. Funds the receipt account.
+///   1. `[WRITE]` Group account.
+///   2. `[WRITE]` Proposal account that holds instruction to be executed.
+///   3. `[WRITE]` Protected group account. Used to transfer lamports back to as the proposal is closed.
+///   4. `[WRITE]` Receipt account to create. Key must be a PDA seeded by the proposal account key.
+///   5. `[]` System program account.
+///   6. `[]` Proposed instruction program account.
+///   7. ..7+N `[]` N accounts needed for proposed instruction to succeed.
+#[derive(Debug, BorshDeserialize, BorshSerialize)]
+pub struct ExecuteWithReceiptInstruction {
+    /// Amount of lamports to fund the receipt account.
+    pub lamports: u64,
+}
+
 #[derive(BorshDeserialize, BorshSerialize)]
 pub enum MultiSigInstruction {
     Init(InitInstruction),
     Propose(ProposeInstruction),
     Approve(ApproveInstruction),
     CloseProposal(CloseProposalInstruction),
+    UpdateGroup(UpdateGroupInstruction),
+    Execute(ExecuteInstruction),
+    RevokeApproval(RevokeApprovalInstruction),
+    Reject(RejectInstruction),
+    CreateVault(CreateVaultInstruction),
+    CreateDraft(CreateDraftInstruction),
+    AppendToDraft(AppendToDraftInstruction),
+    FinalizeDraft(FinalizeDraftInstruction),
+    Migrate(MigrateInstruction),
+    CreateSpendingLimit(CreateSpendingLimitInstruction),
+    RemoveSpendingLimit(RemoveSpendingLimitInstruction),
+    SpendWithinLimit(SpendWithinLimitInstruction),
+    ApproveWithSignatures(ApproveWithSignaturesInstruction),
+    ExecuteWithReceipt(ExecuteWithReceiptInstruction),
 }
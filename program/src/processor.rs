@@ -1,26 +1,57 @@
 use crate::error::Error;
-use std::convert::TryInto;
+use std::convert::{TryFrom, TryInto};
 
-use solana_program::msg;
-use solana_program::program::invoke_signed;
+use solana_program::program::{invoke, invoke_signed};
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
+    clock::Clock,
     hash::hash,
     program_error::ProgramError,
     pubkey::Pubkey,
-    system_instruction::create_account,
+    rent::Rent,
+    system_instruction::{self, create_account},
+    sysvar::{instructions, Sysvar},
 };
 
 use borsh::{BorshDeserialize, BorshSerialize};
 
-use crate::instruction::{InitInstruction, MultiSigInstruction, ProposeInstruction};
-use crate::state::{AccountType, GroupData, ProposalConfig, ProposalData, ProposalState};
-use crate::utils::{read_account_data, write_account_data, write_serialized_data};
+use crate::ed25519;
+use crate::event::Event;
+use crate::instruction::{
+    AppendToDraftInstruction, CreateDraftInstruction, CreateSpendingLimitInstruction,
+    CreateVaultInstruction, InitInstruction, MultiSigInstruction, ProposeInstruction,
+    SpendWithinLimitInstruction, UpdateGroupInstruction,
+};
+use crate::state::{
+    is_unversioned, AccountType, ApprovalMessage, GroupAccount, GroupVault, ProposalConfig,
+    ProposalData, ProposalHeader, ProposalReceipt, ProposalSeed, ProposalState, ProposalStatus,
+    ProposedInstruction, SpendingLimit, Versioned, ACCOUNT_HEADER_LEN,
+};
+use crate::token;
+use crate::utils::{
+    grow_account_data, read_account_data, write_account_data, write_account_header,
+    write_serialized_data,
+};
 
 pub(crate) mod pda_tag {
     pub const GROUP: &[u8] = &[0];
     pub const PROPOSAL: &[u8] = &[1];
     pub const PROTECTED: &[u8] = &[2];
+    pub const SPENDING_LIMIT: &[u8] = &[3];
+    pub const RECEIPT: &[u8] = &[4];
+}
+
+/// Seeds of a group vault without the bump. Vault 0 is the group's original protected account.
+pub(crate) fn vault_seeds<'a>(
+    group_key: &'a Pubkey,
+    index: u16,
+    index_bytes: &'a [u8; 2],
+) -> Vec<&'a [u8]> {
+    if index == 0 {
+        vec![pda_tag::PROTECTED, group_key.as_ref()]
+    } else {
+        vec![pda_tag::PROTECTED, group_key.as_ref(), index_bytes]
+    }
 }
 
 pub struct Processor<'a, 'b> {
@@ -38,6 +69,26 @@ impl Processor<'_, '_> {
             MultiSigInstruction::Propose(data) => self.propose(data),
             MultiSigInstruction::Approve(_) => self.approve(),
             MultiSigInstruction::CloseProposal(_) => self.close_proposal(),
+            MultiSigInstruction::Execute(_) => self.execute(None),
+            MultiSigInstruction::RevokeApproval(_) => self.revoke_approval(),
+            MultiSigInstruction::Reject(_) => self.reject(),
+            MultiSigInstruction::CreateVault(instruction) => self.create_vault(instruction),
+            MultiSigInstruction::CreateDraft(instruction) => self.create_draft(instruction),
+            MultiSigInstruction::AppendToDraft(instruction) => self.append_to_draft(instruction),
+            MultiSigInstruction::FinalizeDraft(_) => self.finalize_draft(),
+            MultiSigInstruction::UpdateGroup(instruction) => self.update_group(instruction),
+            MultiSigInstruction::Migrate(_) => self.migrate(),
+            MultiSigInstruction::CreateSpendingLimit(instruction) => {
+                self.create_spending_limit(instruction)
+            }
+            MultiSigInstruction::RemoveSpendingLimit(_) => self.remove_spending_limit(),
+            MultiSigInstruction::SpendWithinLimit(instruction) => {
+                self.spend_within_limit(instruction)
+            }
+            MultiSigInstruction::ApproveWithSignatures(_) => self.approve_with_signatures(),
+            MultiSigInstruction::ExecuteWithReceipt(instruction) => {
+                self.execute(Some(instruction.lamports))
+            }
         }
     }
 
@@ -49,6 +100,7 @@ impl Processor<'_, '_> {
         let InitInstruction {
             group_data: data,
             lamports,
+            space,
             protected_account_config: init_protected,
         } = instruction;
         let accounts_iter = &mut accounts.iter();
@@ -61,33 +113,9 @@ impl Processor<'_, '_> {
             return Err(ProgramError::MissingRequiredSignature.into());
         }
 
-        if data.threshold == 0 {
-            return Err(Error::ZeroThreshold);
-        }
-        // To make sure proposal bit mask is long enough.
-        if data.members.len() > 64 {
-            return Err(Error::TooManyMembers);
-        }
-        if data.members.is_empty() {
-            return Err(Error::NoMembers);
-        }
-        for member in &data.members {
-            if member.weight == 0 {
-                return Err(Error::ZeroWeight);
-            }
-        }
-        let weight_sum: u32 = data
-            .members
-            .iter()
-            .map(|m| m.weight)
-            .fold(0, |sum, weight| sum.saturating_add(weight));
-        if weight_sum < data.threshold {
-            return Err(Error::UnreachableThreshold);
-        }
-
-        let serialized_data = data.try_to_vec().map_err(Error::Serialize)?;
-        let group_seed = hash(&serialized_data);
+        data.validate()?;
 
+        let group_seed = hash(&data.try_to_vec().map_err(Error::Serialize)?);
         let (addr, nonce) =
             Pubkey::find_program_address(&[pda_tag::GROUP, group_seed.as_ref()], program_id);
 
@@ -95,12 +123,28 @@ impl Processor<'_, '_> {
             return Err(Error::InvalidGroupAccountKey);
         }
 
+        // Protected account bump is stored even if the account is created later.
+        let (protected_key, protected_nonce) =
+            Pubkey::find_program_address(&[pda_tag::PROTECTED, addr.as_ref()], program_id);
+        let group = GroupAccount {
+            seed: group_seed,
+            bump: nonce,
+            vaults: vec![GroupVault {
+                index: 0,
+                bump: protected_nonce,
+            }],
+            data,
+            proposal_count: 0,
+            config_version: 0,
+        };
+        let serialized_data = group.try_to_vec().map_err(Error::Serialize)?;
+
         // Create account to hold group data
         let create_instruction = create_account(
             initializer.key,
             &addr,
             lamports,
-            serialized_data.len() as u64 + 1,
+            space.max((serialized_data.len() + ACCOUNT_HEADER_LEN) as u64),
             program_id,
         );
 
@@ -110,13 +154,21 @@ impl Processor<'_, '_> {
             &[&[pda_tag::GROUP, group_seed.as_ref(), &[nonce]]],
         )?;
 
-        write_serialized_data(group_account_info, AccountType::Group, &serialized_data)?;
+        write_serialized_data(
+            group_account_info,
+            AccountType::Group,
+            GroupAccount::VERSION,
+            &serialized_data,
+        )?;
+        Event::GroupCreated {
+            group: addr,
+            threshold: group.data.threshold,
+            member_count: group.data.members.len() as u32,
+        }
+        .emit()?;
 
         if let Some(protected_account_config) = init_protected {
             let protected_account = next_account_info(accounts_iter)?;
-            let (protected_key, nonce) =
-                Pubkey::find_program_address(&[pda_tag::PROTECTED, addr.as_ref()], program_id);
-
             if *protected_account.key != protected_key {
                 return Err(Error::InvalidProtectedAccountKey);
             }
@@ -132,7 +184,7 @@ impl Processor<'_, '_> {
             invoke_signed(
                 &create_instruction,
                 accounts,
-                &[&[pda_tag::PROTECTED, addr.as_ref(), &[nonce]]],
+                &[&[pda_tag::PROTECTED, addr.as_ref(), &[protected_nonce]]],
             )?;
         }
 
@@ -152,73 +204,206 @@ impl Processor<'_, '_> {
         }
 
         let group_account_info = next_account_info(accounts_iter)?;
-        let group_data = check_and_read_group_data(group_account_info, program_id)?;
+        let mut group = check_and_read_group_data(group_account_info, program_id)?;
+        let group_data = &group.data;
 
         let (signer_index, signer_weight) = group_data.weight(signer_account_info.key)?;
 
-        let mut state = ProposalState::new();
+        if let Some(expiry) = data.expiry {
+            if expiry.is_expired(&Clock::get()?) {
+                return Err(Error::ProposalExpired);
+            }
+        }
+
+        group_data.check_programs(&data.instructions, program_id)?;
+        let threshold = group_data.proposal_threshold(&data.instructions, program_id);
+        let mut state = ProposalState::new(group_data.members.len());
         state.add_approval(signer_index, signer_weight)?;
-        if state.current_weight() >= group_data.threshold {
-            let (_protected_pubkey, seed) = Pubkey::find_program_address(
-                &[pda_tag::PROTECTED, group_account_info.key.as_ref()],
-                program_id,
-            );
+        // Proposal approved by the proposer alone is still executed with a separate instruction.
+        if state.current_weight() >= threshold {
+            state.mark_approved(Clock::get()?.unix_timestamp);
+        }
 
-            for instruction in data.instructions {
-                invoke_signed(
-                    &instruction.try_into()?,
-                    accounts,
-                    &[&[pda_tag::PROTECTED, group_account_info.key.as_ref(), &[seed]]],
-                )?;
-            }
-        } else {
-            let proposal_account_info = next_account_info(accounts_iter)?;
+        let proposal_account_info = next_account_info(accounts_iter)?;
 
-            let config = ProposalConfig {
-                group: *group_account_info.key,
-                instructions: data.instructions,
-                author: *signer_account_info.key,
-                salt: data.salt
-            };
-            // Note: This is proposed instruction, not proposal data
-            let serialized_config = config.try_to_vec().map_err(Error::Serialize)?;
-            let proposal_hash = hash(&serialized_config);
-
-            let proposal = ProposalData { config, state };
-
-            let (addr, nonce) = Pubkey::find_program_address(
-                &[pda_tag::PROPOSAL, proposal_hash.as_ref()],
-                program_id,
-            );
+        let current_weight = state.current_weight();
+        let config = ProposalConfig {
+            group: *group_account_info.key,
+            instructions: data.instructions,
+            author: *signer_account_info.key,
+            salt: data.salt,
+            expiry: data.expiry,
+        };
+        create_proposal_account(
+            accounts,
+            program_id,
+            signer_account_info.key,
+            group_account_info,
+            &mut group,
+            proposal_account_info,
+            config,
+            state,
+            threshold,
+            data.lamports,
+            0,
+        )?;
 
-            if *proposal_account_info.key != addr {
-                return Err(Error::InvalidProposalAccountKey);
+        Event::Proposed {
+            group: *group_account_info.key,
+            proposal: *proposal_account_info.key,
+            author: *signer_account_info.key,
+        }
+        .emit()?;
+        Event::Approved {
+            group: *group_account_info.key,
+            proposal: *proposal_account_info.key,
+            member: *signer_account_info.key,
+            weight: signer_weight,
+            current_weight,
+        }
+        .emit()
+    }
+
+    fn create_draft(self, instruction: CreateDraftInstruction) -> Result<(), Error> {
+        let Self {
+            accounts,
+            program_id,
+        } = self;
+        let accounts_iter = &mut accounts.iter();
+
+        let signer_account_info = next_account_info(accounts_iter)?;
+        if !signer_account_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature.into());
+        }
+
+        let group_account_info = next_account_info(accounts_iter)?;
+        let mut group = check_and_read_group_data(group_account_info, program_id)?;
+        group.data.weight(signer_account_info.key)?;
+
+        if let Some(expiry) = instruction.expiry {
+            if expiry.is_expired(&Clock::get()?) {
+                return Err(Error::ProposalExpired);
             }
+        }
 
-            let serialized_data = proposal.try_to_vec().map_err(Error::Serialize)?;
+        let proposal_account_info = next_account_info(accounts_iter)?;
 
-            let create_instruction = create_account(
-                signer_account_info.key,
-                &addr,
-                data.lamports,
-                serialized_data.len() as u64 + 1,
-                program_id,
-            );
+        let config = ProposalConfig {
+            group: *group_account_info.key,
+            instructions: Vec::new(),
+            author: *signer_account_info.key,
+            salt: instruction.salt,
+            expiry: instruction.expiry,
+        };
+        let state = ProposalState::new_draft(group.data.members.len());
+        let threshold = group.data.proposal_threshold(&[], program_id);
+        create_proposal_account(
+            accounts,
+            program_id,
+            signer_account_info.key,
+            group_account_info,
+            &mut group,
+            proposal_account_info,
+            config,
+            state,
+            threshold,
+            instruction.lamports,
+            instruction.space,
+        )?;
 
-            invoke_signed(
-                &create_instruction,
-                accounts,
-                &[&[pda_tag::PROPOSAL, proposal_hash.as_ref(), &[nonce]]],
-            )?;
+        Event::Proposed {
+            group: *group_account_info.key,
+            proposal: *proposal_account_info.key,
+            author: *signer_account_info.key,
+        }
+        .emit()
+    }
 
-            write_serialized_data(
-                proposal_account_info,
-                AccountType::Proposal,
-                &serialized_data,
-            )?;
+    fn append_to_draft(self, instruction: AppendToDraftInstruction) -> Result<(), Error> {
+        let Self {
+            accounts,
+            program_id,
+        } = self;
+        let accounts_iter = &mut accounts.iter();
+
+        let signer_account_info = next_account_info(accounts_iter)?;
+        if !signer_account_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature.into());
         }
 
-        Ok(())
+        let proposal_account_info = next_account_info(accounts_iter)?;
+        let mut proposal = check_and_read_proposal_data(proposal_account_info, program_id)?;
+        if proposal.config.author != *signer_account_info.key {
+            return Err(ProgramError::MissingRequiredSignature.into());
+        }
+        if proposal.state.status() != ProposalStatus::Draft {
+            return Err(Error::ProposalIsNotDraft);
+        }
+
+        proposal
+            .config
+            .instructions
+            .extend(instruction.instructions);
+        write_account_data(proposal_account_info, AccountType::Proposal, &proposal)
+    }
+
+    fn finalize_draft(self) -> Result<(), Error> {
+        let Self {
+            accounts,
+            program_id,
+        } = self;
+        let accounts_iter = &mut accounts.iter();
+
+        let signer_account_info = next_account_info(accounts_iter)?;
+        if !signer_account_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature.into());
+        }
+
+        let group_account_info = next_account_info(accounts_iter)?;
+        let group = check_and_read_group_data(group_account_info, program_id)?;
+        let group_data = &group.data;
+        let (signer_index, signer_weight) = group_data.weight(signer_account_info.key)?;
+
+        let proposal_account_info = next_account_info(accounts_iter)?;
+        let mut proposal = check_and_read_proposal_data(proposal_account_info, program_id)?;
+        if proposal.config.group != *group_account_info.key {
+            return Err(Error::InvalidGroupAccountKey);
+        }
+        group.check_proposal_version(proposal.group_version)?;
+        if proposal.config.author != *signer_account_info.key {
+            return Err(ProgramError::MissingRequiredSignature.into());
+        }
+        if proposal.state.status() != ProposalStatus::Draft {
+            return Err(Error::ProposalIsNotDraft);
+        }
+        let clock = Clock::get()?;
+        if proposal.config.is_expired(&clock) {
+            return Err(Error::ProposalExpired);
+        }
+
+        group_data.check_programs(&proposal.config.instructions, program_id)?;
+        // Instructions can't change anymore, so the threshold they need is fixed too.
+        proposal.threshold =
+            group_data.proposal_threshold(&proposal.config.instructions, program_id);
+        proposal.state.mark_active();
+        proposal.state.add_approval(signer_index, signer_weight)?;
+        if proposal.state.current_weight() >= proposal.threshold {
+            proposal.state.mark_approved(clock.unix_timestamp);
+        }
+
+        write_account_header(
+            proposal_account_info,
+            AccountType::Proposal,
+            &proposal.header(),
+        )?;
+        Event::Approved {
+            group: *group_account_info.key,
+            proposal: *proposal_account_info.key,
+            member: *signer_account_info.key,
+            weight: signer_weight,
+            current_weight: proposal.state.current_weight(),
+        }
+        .emit()
     }
 
     fn approve(self) -> Result<(), Error> {
@@ -234,51 +419,324 @@ impl Processor<'_, '_> {
         }
 
         let group_account_info = next_account_info(accounts_iter)?;
-        let group_data = check_and_read_group_data(group_account_info, program_id)?;
+        let group = check_and_read_group_data(group_account_info, program_id)?;
+        let group_data = &group.data;
         let (signer_index, signer_weight) = group_data.weight(signer_account_info.key)?;
 
+        let proposal_account_info = next_account_info(accounts_iter)?;
+        let mut proposal = check_and_read_proposal_header(proposal_account_info, program_id)?;
+
+        if proposal.group != *group_account_info.key {
+            return Err(Error::InvalidGroupAccountKey);
+        }
+        group.check_proposal_version(proposal.group_version)?;
+        proposal.state.check_open_for_votes()?;
+        let clock = Clock::get()?;
+        if proposal.is_expired(&clock) {
+            return Err(Error::ProposalExpired);
+        }
+
+        proposal.state.add_approval(signer_index, signer_weight)?;
+        if proposal.state.current_weight() >= proposal.threshold(group_data)
+            && proposal.state.status() == ProposalStatus::Active
+        {
+            proposal.state.mark_approved(clock.unix_timestamp);
+        }
+
+        write_account_header(proposal_account_info, AccountType::Proposal, &proposal)?;
+        Event::Approved {
+            group: *group_account_info.key,
+            proposal: *proposal_account_info.key,
+            member: *signer_account_info.key,
+            weight: signer_weight,
+            current_weight: proposal.state.current_weight(),
+        }
+        .emit()
+    }
+
+    fn approve_with_signatures(self) -> Result<(), Error> {
+        let Self {
+            accounts,
+            program_id,
+        } = self;
+        let accounts_iter = &mut accounts.iter();
+
+        let group_account_info = next_account_info(accounts_iter)?;
+        let group = check_and_read_group_data(group_account_info, program_id)?;
+        let group_data = &group.data;
+
         let proposal_account_info = next_account_info(accounts_iter)?;
         let proposal_data = check_and_read_proposal_data(proposal_account_info, program_id)?;
-        let ProposalData {
-            config: proposal_config,
-            state: mut proposal_state,
-        } = proposal_data;
+        let message = ApprovalMessage::new(
+            program_id,
+            proposal_account_info.key,
+            &proposal_data.config,
+            proposal_data.approval_nonce,
+        )?
+        .to_bytes()?;
+        let mut proposal = proposal_data.header();
+        if proposal.group != *group_account_info.key {
+            return Err(Error::InvalidGroupAccountKey);
+        }
+        group.check_proposal_version(proposal.group_version)?;
+        proposal.state.check_open_for_votes()?;
+        let clock = Clock::get()?;
+        if proposal.is_expired(&clock) {
+            return Err(Error::ProposalExpired);
+        }
 
-        let protected_account_info = next_account_info(accounts_iter)?;
+        let instructions_account_info = next_account_info(accounts_iter)?;
+        if !instructions::check_id(instructions_account_info.key) {
+            return Err(ProgramError::UnsupportedSysvar.into());
+        }
+        let signers = {
+            let data = instructions_account_info.try_borrow_data()?;
+            let index = instructions::load_current_index(&data)
+                .checked_sub(1)
+                .ok_or(Error::InvalidSignatureInstruction)?;
+            let instruction = instructions::load_instruction_at(index as usize, &data)
+                .map_err(|_| Error::InvalidSignatureInstruction)?;
+            ed25519::signers(&instruction, index, &message)
+                .ok_or(Error::InvalidSignatureInstruction)?
+        };
+
+        let mut events = Vec::with_capacity(signers.len());
+        for signer in signers {
+            let (signer_index, signer_weight) = group_data.weight(&signer)?;
+            proposal.state.add_approval(signer_index, signer_weight)?;
+            events.push(Event::Approved {
+                group: *group_account_info.key,
+                proposal: *proposal_account_info.key,
+                member: signer,
+                weight: signer_weight,
+                current_weight: proposal.state.current_weight(),
+            });
+        }
+        if proposal.state.current_weight() >= proposal.threshold(group_data)
+            && proposal.state.status() == ProposalStatus::Active
+        {
+            proposal.state.mark_approved(clock.unix_timestamp);
+        }
+
+        write_account_header(proposal_account_info, AccountType::Proposal, &proposal)?;
+        events.iter().try_for_each(Event::emit)
+    }
+
+    fn revoke_approval(self) -> Result<(), Error> {
+        let Self {
+            accounts,
+            program_id,
+        } = self;
+        let accounts_iter = &mut accounts.iter();
+
+        let signer_account_info = next_account_info(accounts_iter)?;
+        if !signer_account_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature.into());
+        }
+
+        let group_account_info = next_account_info(accounts_iter)?;
+        let group = check_and_read_group_data(group_account_info, program_id)?;
+        let group_data = &group.data;
+        let (signer_index, signer_weight) = group_data.weight(signer_account_info.key)?;
+
+        let proposal_account_info = next_account_info(accounts_iter)?;
+        let mut proposal = check_and_read_proposal_header(proposal_account_info, program_id)?;
 
-        if proposal_config.group != *group_account_info.key {
+        if proposal.group != *group_account_info.key {
             return Err(Error::InvalidGroupAccountKey);
         }
+        group.check_proposal_version(proposal.group_version)?;
+        proposal.state.check_open_for_votes()?;
+
+        proposal
+            .state
+            .remove_approval(signer_index, signer_weight)?;
+        // Invalidates approval signatures made so far, the revoked one among them.
+        proposal.approval_nonce += 1;
+        if proposal.state.status() == ProposalStatus::Approved
+            && proposal.state.current_weight() < proposal.threshold(group_data)
+        {
+            proposal.state.mark_active();
+        }
 
-        proposal_state.add_approval(signer_index, signer_weight)?;
-        if proposal_state.current_weight() >= group_data.threshold {
-            let (_protected_pubkey, seed) = Pubkey::find_program_address(
-                &[pda_tag::PROTECTED, group_account_info.key.as_ref()],
-                program_id,
-            );
+        write_account_header(proposal_account_info, AccountType::Proposal, &proposal)?;
+        Event::ApprovalRevoked {
+            group: *group_account_info.key,
+            proposal: *proposal_account_info.key,
+            member: *signer_account_info.key,
+            weight: signer_weight,
+            current_weight: proposal.state.current_weight(),
+        }
+        .emit()
+    }
+
+    fn reject(self) -> Result<(), Error> {
+        let Self {
+            accounts,
+            program_id,
+        } = self;
+        let accounts_iter = &mut accounts.iter();
+
+        let signer_account_info = next_account_info(accounts_iter)?;
+        if !signer_account_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature.into());
+        }
+
+        let group_account_info = next_account_info(accounts_iter)?;
+        let group = check_and_read_group_data(group_account_info, program_id)?;
+        let group_data = &group.data;
+        let (signer_index, signer_weight) = group_data.weight(signer_account_info.key)?;
+
+        let proposal_account_info = next_account_info(accounts_iter)?;
+        let mut proposal = check_and_read_proposal_header(proposal_account_info, program_id)?;
+
+        let author_account_info = next_account_info(accounts_iter)?;
+        let receipt_account_info = accounts_iter.next();
 
-            for instruction in proposal_config.instructions {
-                invoke_signed(
-                    &instruction.try_into()?,
+        if proposal.group != *group_account_info.key {
+            return Err(Error::InvalidGroupAccountKey);
+        }
+        group.check_proposal_version(proposal.group_version)?;
+        if proposal.author != *author_account_info.key {
+            return Err(Error::InvalidDestinationAccountKey);
+        }
+        proposal.state.check_open_for_votes()?;
+
+        proposal.state.add_rejection(signer_index, signer_weight)?;
+        Event::Rejected {
+            group: *group_account_info.key,
+            proposal: *proposal_account_info.key,
+            member: *signer_account_info.key,
+            weight: signer_weight,
+            rejected_weight: proposal.state.rejected_weight(),
+        }
+        .emit()?;
+        let reachable_weight = group_data
+            .total_weight()
+            .saturating_sub(proposal.state.rejected_weight());
+        if reachable_weight < proposal.threshold(group_data) {
+            Event::Ended {
+                group: *group_account_info.key,
+                proposal: *proposal_account_info.key,
+                status: ProposalStatus::Rejected,
+            }
+            .emit()?;
+            if let Some(receipt_account_info) = receipt_account_info {
+                create_receipt(
                     accounts,
-                    &[&[pda_tag::PROTECTED, group_account_info.key.as_ref(), &[seed]]],
+                    program_id,
+                    proposal_account_info,
+                    receipt_account_info,
+                    proposal_account_info,
+                    None,
+                    ProposalStatus::Rejected,
                 )?;
             }
+            return close_proposal_account(proposal_account_info, author_account_info);
+        }
+
+        write_account_header(proposal_account_info, AccountType::Proposal, &proposal)
+    }
+
+    /// Execute an approved proposal, keeping a receipt funded with `receipt_lamports` if given.
+    fn execute(self, receipt_lamports: Option<u64>) -> Result<(), Error> {
+        let Self {
+            accounts,
+            program_id,
+        } = self;
+        let accounts_iter = &mut accounts.iter();
+
+        let signer_account_info = next_account_info(accounts_iter)?;
+        if !signer_account_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature.into());
+        }
+
+        let group_account_info = next_account_info(accounts_iter)?;
+        let group = check_and_read_group_data(group_account_info, program_id)?;
+        let group_data = &group.data;
+
+        let proposal_account_info = next_account_info(accounts_iter)?;
+        let mut proposal = check_and_read_proposal_data(proposal_account_info, program_id)?;
+
+        let protected_account_info = next_account_info(accounts_iter)?;
+        let receipt_account_info = match receipt_lamports {
+            Some(_) => {
+                let receipt_account_info = next_account_info(accounts_iter)?;
+                let _sys_program_account = next_account_info(accounts_iter)?;
+                Some(receipt_account_info)
+            }
+            None => None,
+        };
 
-            for i in &mut **proposal_account_info.data.borrow_mut() {
-                *i = 0;
+        if proposal.config.group != *group_account_info.key {
+            return Err(Error::InvalidGroupAccountKey);
+        }
+        group.check_proposal_version(proposal.group_version)?;
+        let clock = Clock::get()?;
+        if proposal.config.is_expired(&clock) {
+            return Err(Error::ProposalExpired);
+        }
+
+        let approved_at = match proposal.state.status() {
+            ProposalStatus::Draft | ProposalStatus::Active => {
+                return Err(Error::ProposalNotApproved)
             }
+            ProposalStatus::Executed => return Err(Error::ProposalAlreadyExecuted),
+            ProposalStatus::Rejected | ProposalStatus::Cancelled | ProposalStatus::Expired => {
+                return Err(Error::ProposalClosed)
+            }
+            ProposalStatus::Approved => proposal
+                .state
+                .approved_at()
+                .ok_or(Error::ProposalNotApproved)?,
+        };
+        if proposal.state.current_weight() < proposal.threshold(group_data) {
+            return Err(Error::ProposalNotApproved);
+        }
+        // Policy might have changed since the proposal was created.
+        group_data.check_programs(&proposal.config.instructions, program_id)?;
+        // Groups are validated to have a delay that fits, but it doesn't hurt to check.
+        let execution_delay =
+            i64::try_from(group_data.execution_delay).map_err(|_| Error::InvalidExecutionDelay)?;
+        let executable_at = approved_at.saturating_add(execution_delay);
+        if clock.unix_timestamp < executable_at {
+            return Err(Error::ExecutionDelayNotElapsed);
+        }
 
-            transfer_lamports_from_proposal(proposal_account_info, protected_account_info);
-        } else {
-            let proposal_data = ProposalData {
-                config: proposal_config,
-                state: proposal_state,
-            };
-            write_account_data(proposal_account_info, AccountType::Proposal, &proposal_data)?;
+        // Store the status before invoking, so proposed instructions can't execute it again.
+        proposal.state.mark_executed();
+        write_account_header(
+            proposal_account_info,
+            AccountType::Proposal,
+            &proposal.header(),
+        )?;
+        let instructions = proposal.config.instructions;
+
+        invoke_proposed_instructions(
+            instructions,
+            accounts,
+            group_account_info.key,
+            &group,
+            program_id,
+        )?;
+        Event::Executed {
+            group: *group_account_info.key,
+            proposal: *proposal_account_info.key,
         }
+        .emit()?;
 
-        Ok(())
+        if let Some(receipt_account_info) = receipt_account_info {
+            create_receipt(
+                accounts,
+                program_id,
+                proposal_account_info,
+                receipt_account_info,
+                signer_account_info,
+                receipt_lamports,
+                ProposalStatus::Executed,
+            )?;
+        }
+        close_proposal_account(proposal_account_info, protected_account_info)
     }
 
     fn close_proposal(self) -> Result<(), Error> {
@@ -294,39 +752,479 @@ impl Processor<'_, '_> {
         }
 
         let proposal_account_info = next_account_info(accounts_iter)?;
-        let proposal_data = check_and_read_proposal_data(proposal_account_info, program_id)?;
-        let proposal_config = proposal_data.config;
-        let author = proposal_config.author;
+        let proposal = check_and_read_proposal_header(proposal_account_info, program_id)?;
+        let author = proposal.author;
+
+        let destination_account_info = next_account_info(accounts_iter)?;
+        let receipt_account_info = accounts_iter.next();
+
+        let expired = proposal.is_expired(&Clock::get()?);
         if signer_account_info.key != &author {
-            return Err(ProgramError::MissingRequiredSignature.into());
+            // Anyone may clean up an expired proposal, but the rent goes back to its author.
+            if !expired {
+                return Err(ProgramError::MissingRequiredSignature.into());
+            }
+            if destination_account_info.key != &author {
+                return Err(Error::InvalidDestinationAccountKey);
+            }
+        }
+
+        let status = if expired {
+            ProposalStatus::Expired
+        } else {
+            ProposalStatus::Cancelled
+        };
+        Event::Ended {
+            group: proposal.group,
+            proposal: *proposal_account_info.key,
+            status,
+        }
+        .emit()?;
+        if let Some(receipt_account_info) = receipt_account_info {
+            create_receipt(
+                accounts,
+                program_id,
+                proposal_account_info,
+                receipt_account_info,
+                proposal_account_info,
+                None,
+                status,
+            )?;
+        }
+        close_proposal_account(proposal_account_info, destination_account_info)
+    }
+
+    fn update_group(self, instruction: UpdateGroupInstruction) -> Result<(), Error> {
+        let Self {
+            accounts,
+            program_id,
+        } = self;
+        let accounts_iter = &mut accounts.iter();
+
+        let protected_account_info = next_account_info(accounts_iter)?;
+        let group_account_info = next_account_info(accounts_iter)?;
+        let mut group = check_and_read_group_data(group_account_info, program_id)?;
+
+        check_protected_signer(
+            protected_account_info,
+            group_account_info.key,
+            &group,
+            program_id,
+        )?;
+
+        instruction.group_data.validate()?;
+        group.data = instruction.group_data;
+        // Open proposals count votes by member index, which no longer holds.
+        group.config_version += 1;
+        write_account_data(group_account_info, AccountType::Group, &group)?;
+        Event::GroupUpdated {
+            group: *group_account_info.key,
+            threshold: group.data.threshold,
+            member_count: group.data.members.len() as u32,
+        }
+        .emit()
+    }
+
+    fn create_vault(self, instruction: CreateVaultInstruction) -> Result<(), Error> {
+        let Self {
+            accounts,
+            program_id,
+        } = self;
+        let CreateVaultInstruction { index, config } = instruction;
+        let accounts_iter = &mut accounts.iter();
+
+        let protected_account_info = next_account_info(accounts_iter)?;
+        let group_account_info = next_account_info(accounts_iter)?;
+        let mut group = check_and_read_group_data(group_account_info, program_id)?;
+        check_protected_signer(
+            protected_account_info,
+            group_account_info.key,
+            &group,
+            program_id,
+        )?;
+
+        let funder_account_info = next_account_info(accounts_iter)?;
+        let vault_account_info = next_account_info(accounts_iter)?;
+        let _sys_program_account = next_account_info(accounts_iter)?;
+
+        let index_bytes = index.to_le_bytes();
+        let seeds = vault_seeds(group_account_info.key, index, &index_bytes);
+        let (vault_key, nonce) = Pubkey::find_program_address(&seeds, program_id);
+        if *vault_account_info.key != vault_key {
+            return Err(Error::InvalidProtectedAccountKey);
+        }
+        // Vault 0 is the protected account, created along with the group. Groups of the first
+        // program release don't store its bump.
+        if index == 0 || group.vault_bump(index).is_some() {
+            return Err(Error::VaultAlreadyExists);
+        }
+
+        // Vault address is known in advance and anyone can send lamports to it, which would make
+        // create_account fail. Top it up, allocate and assign instead.
+        let lamports = config
+            .lamports
+            .saturating_sub(vault_account_info.lamports());
+        if lamports > 0 {
+            invoke(
+                &system_instruction::transfer(funder_account_info.key, &vault_key, lamports),
+                accounts,
+            )?;
+        }
+        let nonce = [nonce];
+        let mut signer_seeds = seeds;
+        signer_seeds.push(&nonce);
+        invoke_signed(
+            &system_instruction::allocate(&vault_key, config.space),
+            accounts,
+            &[&signer_seeds],
+        )?;
+        invoke_signed(
+            &system_instruction::assign(&vault_key, &config.owner),
+            accounts,
+            &[&signer_seeds],
+        )?;
+
+        group.vaults.push(GroupVault {
+            index,
+            bump: nonce[0],
+        });
+        write_account_data(group_account_info, AccountType::Group, &group)?;
+        Event::VaultCreated {
+            group: *group_account_info.key,
+            index,
+            vault: vault_key,
+        }
+        .emit()
+    }
+
+    fn migrate(self) -> Result<(), Error> {
+        let Self {
+            accounts,
+            program_id,
+        } = self;
+        let accounts_iter = &mut accounts.iter();
+
+        let account_info = next_account_info(accounts_iter)?;
+        if account_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId.into());
+        }
+        let account_type = match account_info.data.borrow().first() {
+            Some(account_type) => AccountType::try_from(*account_type)?,
+            None => return Err(Error::EmptyAccountData),
+        };
+
+        let funder_account_info = accounts_iter.next();
+
+        // Data is decoded from whatever layout it was written with and written in the current one.
+        match account_type {
+            AccountType::Group => {
+                let group = check_and_read_group_data(account_info, program_id)?;
+                rewrite_account(
+                    accounts,
+                    account_info,
+                    funder_account_info,
+                    AccountType::Group,
+                    &group,
+                )
+            }
+            AccountType::Proposal => {
+                let proposal = check_and_read_proposal_data(account_info, program_id)?;
+                rewrite_account(
+                    accounts,
+                    account_info,
+                    funder_account_info,
+                    AccountType::Proposal,
+                    &proposal,
+                )
+            }
+            AccountType::SpendingLimit => {
+                let limit = check_and_read_spending_limit(account_info, program_id)?;
+                rewrite_account(
+                    accounts,
+                    account_info,
+                    funder_account_info,
+                    AccountType::SpendingLimit,
+                    &limit,
+                )
+            }
+            AccountType::Receipt => {
+                let receipt =
+                    read_account_data::<ProposalReceipt>(AccountType::Receipt, account_info)?;
+                rewrite_account(
+                    accounts,
+                    account_info,
+                    funder_account_info,
+                    AccountType::Receipt,
+                    &receipt,
+                )
+            }
+        }
+    }
+
+    fn create_spending_limit(
+        self,
+        instruction: CreateSpendingLimitInstruction,
+    ) -> Result<(), Error> {
+        let Self {
+            accounts,
+            program_id,
+        } = self;
+        let CreateSpendingLimitInstruction {
+            index,
+            config,
+            lamports,
+        } = instruction;
+        let accounts_iter = &mut accounts.iter();
+
+        let protected_account_info = next_account_info(accounts_iter)?;
+        let group_account_info = next_account_info(accounts_iter)?;
+        let group = check_and_read_group_data(group_account_info, program_id)?;
+        check_protected_signer(
+            protected_account_info,
+            group_account_info.key,
+            &group,
+            program_id,
+        )?;
+
+        let funder_account_info = next_account_info(accounts_iter)?;
+        let limit_account_info = next_account_info(accounts_iter)?;
+        let _sys_program_account = next_account_info(accounts_iter)?;
+
+        config.validate()?;
+        // Vault 0 is the protected account, groups of the first program release don't store it.
+        if config.vault != 0 && group.vault_bump(config.vault).is_none() {
+            return Err(Error::InvalidProtectedAccountKey);
+        }
+
+        let index_bytes = index.to_le_bytes();
+        let (limit_key, bump) = Pubkey::find_program_address(
+            &[
+                pda_tag::SPENDING_LIMIT,
+                group_account_info.key.as_ref(),
+                &index_bytes,
+            ],
+            program_id,
+        );
+        if *limit_account_info.key != limit_key {
+            return Err(Error::InvalidSpendingLimitAccountKey);
+        }
+
+        let limit = SpendingLimit {
+            group: *group_account_info.key,
+            index,
+            bump,
+            config,
+            period_start: Clock::get()?.unix_timestamp,
+            spent: 0,
+        };
+        let serialized_data = limit.try_to_vec().map_err(Error::Serialize)?;
+
+        let create_instruction = create_account(
+            funder_account_info.key,
+            &limit_key,
+            lamports,
+            (serialized_data.len() + ACCOUNT_HEADER_LEN) as u64,
+            program_id,
+        );
+        invoke_signed(
+            &create_instruction,
+            accounts,
+            &[&[
+                pda_tag::SPENDING_LIMIT,
+                group_account_info.key.as_ref(),
+                &index_bytes,
+                &[bump],
+            ]],
+        )?;
+
+        write_serialized_data(
+            limit_account_info,
+            AccountType::SpendingLimit,
+            SpendingLimit::VERSION,
+            &serialized_data,
+        )?;
+        Event::SpendingLimitCreated {
+            group: *group_account_info.key,
+            index,
+            limit: limit_key,
+        }
+        .emit()
+    }
+
+    fn remove_spending_limit(self) -> Result<(), Error> {
+        let Self {
+            accounts,
+            program_id,
+        } = self;
+        let accounts_iter = &mut accounts.iter();
+
+        let protected_account_info = next_account_info(accounts_iter)?;
+        let group_account_info = next_account_info(accounts_iter)?;
+        let group = check_and_read_group_data(group_account_info, program_id)?;
+        check_protected_signer(
+            protected_account_info,
+            group_account_info.key,
+            &group,
+            program_id,
+        )?;
+
+        let limit_account_info = next_account_info(accounts_iter)?;
+        let limit = check_and_read_spending_limit(limit_account_info, program_id)?;
+        if limit.group != *group_account_info.key {
+            return Err(Error::InvalidGroupAccountKey);
         }
 
         let destination_account_info = next_account_info(accounts_iter)?;
+        close_account(limit_account_info, destination_account_info);
+        Event::SpendingLimitRemoved {
+            group: *group_account_info.key,
+            limit: *limit_account_info.key,
+        }
+        .emit()
+    }
 
-        for i in &mut **proposal_account_info.data.borrow_mut() {
-            *i = 0;
+    fn spend_within_limit(self, instruction: SpendWithinLimitInstruction) -> Result<(), Error> {
+        let Self {
+            accounts,
+            program_id,
+        } = self;
+        let SpendWithinLimitInstruction { amount } = instruction;
+        let accounts_iter = &mut accounts.iter();
+
+        let signer_account_info = next_account_info(accounts_iter)?;
+        if !signer_account_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature.into());
         }
 
-        transfer_lamports_from_proposal(proposal_account_info, destination_account_info);
-        Ok(())
+        let group_account_info = next_account_info(accounts_iter)?;
+        let group = check_and_read_group_data(group_account_info, program_id)?;
+        group.data.weight(signer_account_info.key)?;
+
+        let limit_account_info = next_account_info(accounts_iter)?;
+        let mut limit = check_and_read_spending_limit(limit_account_info, program_id)?;
+        if limit.group != *group_account_info.key {
+            return Err(Error::InvalidGroupAccountKey);
+        }
+        if !limit.config.allows(signer_account_info.key) {
+            return Err(Error::Unauthorized);
+        }
+
+        let vault_account_info = next_account_info(accounts_iter)?;
+        let (vault_key, vault_bump) = vault_address(
+            group_account_info.key,
+            &group,
+            limit.config.vault,
+            program_id,
+        )?;
+        if *vault_account_info.key != vault_key {
+            return Err(Error::InvalidProtectedAccountKey);
+        }
+        let destination_account_info = next_account_info(accounts_iter)?;
+        let _program_account = next_account_info(accounts_iter)?;
+
+        let transfer_instruction = match limit.config.mint {
+            None => system_instruction::transfer(&vault_key, destination_account_info.key, amount),
+            Some(mint) => {
+                let source_account_info = next_account_info(accounts_iter)?;
+                if *source_account_info.owner != token::id()
+                    || token::account_mint_and_owner(&source_account_info.data.borrow())
+                        != Some((mint, vault_key))
+                {
+                    return Err(Error::InvalidTokenAccount);
+                }
+                token::transfer(
+                    source_account_info.key,
+                    destination_account_info.key,
+                    &vault_key,
+                    amount,
+                )
+            }
+        };
+
+        limit.spend(amount, Clock::get()?.unix_timestamp)?;
+        write_account_data(limit_account_info, AccountType::SpendingLimit, &limit)?;
+
+        let index_bytes = limit.config.vault.to_le_bytes();
+        let bump = [vault_bump];
+        let mut signer_seeds =
+            vault_seeds(group_account_info.key, limit.config.vault, &index_bytes);
+        signer_seeds.push(&bump);
+        invoke_signed(&transfer_instruction, accounts, &[&signer_seeds])?;
+
+        Event::SpentWithinLimit {
+            group: *group_account_info.key,
+            limit: *limit_account_info.key,
+            member: *signer_account_info.key,
+            amount,
+            spent: limit.spent,
+        }
+        .emit()
+    }
+}
+
+/// Make sure the group's protected account (vault 0) signed, i.e. the group approved the call.
+fn check_protected_signer(
+    protected_account_info: &AccountInfo,
+    group_key: &Pubkey,
+    group: &GroupAccount,
+    program_id: &Pubkey,
+) -> Result<(), Error> {
+    let (protected_key, _) = vault_address(group_key, group, 0, program_id)?;
+    if *protected_account_info.key != protected_key {
+        return Err(Error::InvalidProtectedAccountKey);
+    }
+    if !protected_account_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature.into());
     }
+    Ok(())
 }
 
-fn check_and_read_group_data(info: &AccountInfo, program_id: &Pubkey) -> Result<GroupData, Error> {
+/// Key and bump of a group vault. Vaults created by the program have their bump stored in the
+/// group, the bump of any other vault has to be searched for.
+fn vault_address(
+    group_key: &Pubkey,
+    group: &GroupAccount,
+    index: u16,
+    program_id: &Pubkey,
+) -> Result<(Pubkey, u8), Error> {
+    let index_bytes = index.to_le_bytes();
+    let mut seeds = vault_seeds(group_key, index, &index_bytes);
+    match group.vault_bump(index) {
+        Some(bump) => {
+            let bump = [bump];
+            seeds.push(&bump);
+            let key = Pubkey::create_program_address(&seeds, program_id)
+                .map_err(|_| Error::InvalidProtectedAccountKey)?;
+            Ok((key, bump[0]))
+        }
+        None => Ok(Pubkey::find_program_address(&seeds, program_id)),
+    }
+}
+
+fn check_and_read_group_data(
+    info: &AccountInfo,
+    program_id: &Pubkey,
+) -> Result<GroupAccount, Error> {
     if info.owner != program_id {
         return Err(ProgramError::IncorrectProgramId.into());
     }
 
-    let group_data = read_account_data::<GroupData>(AccountType::Group, info)?;
-
-    let group_hash = hash(&info.data.borrow()[1..]);
+    let mut group = read_account_data::<GroupAccount>(AccountType::Group, info)?;
+    // Groups of the first program release don't store the bump of their key.
+    if is_unversioned(&info.data.borrow()) {
+        group.bump =
+            Pubkey::find_program_address(&[pda_tag::GROUP, group.seed.as_ref()], program_id).1;
+    }
 
-    let (group_pda, _) =
-        Pubkey::find_program_address(&[pda_tag::GROUP, group_hash.as_ref()], program_id);
+    let group_pda = Pubkey::create_program_address(
+        &[pda_tag::GROUP, group.seed.as_ref(), &[group.bump]],
+        program_id,
+    )
+    .map_err(|_| Error::InvalidGroupAccountKey)?;
     if group_pda != *info.key {
         return Err(Error::InvalidGroupAccountKey);
     }
-    Ok(group_data)
+    Ok(group)
 }
 
 pub fn check_and_read_proposal_data(
@@ -337,35 +1235,339 @@ pub fn check_and_read_proposal_data(
         return Err(ProgramError::IncorrectProgramId.into());
     }
 
-    let proposal_data = read_account_data::<ProposalData>(AccountType::Proposal, info)?;
+    let mut proposal_data = read_account_data::<ProposalData>(AccountType::Proposal, info)?;
+    proposal_data.bump = proposal_bump(info, &proposal_data.seed, proposal_data.bump, program_id);
+    check_proposal_key(
+        info,
+        &proposal_data.seed,
+        &proposal_data.config.group,
+        proposal_data.bump,
+        program_id,
+    )?;
+    Ok(proposal_data)
+}
+
+/// Read only the fixed-size header of a proposal, leaving its instructions alone.
+fn check_and_read_proposal_header(
+    info: &AccountInfo,
+    program_id: &Pubkey,
+) -> Result<ProposalHeader, Error> {
+    if info.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId.into());
+    }
+
+    let mut header = read_account_data::<ProposalHeader>(AccountType::Proposal, info)?;
+    header.bump = proposal_bump(info, &header.seed, header.bump, program_id);
+    check_proposal_key(info, &header.seed, &header.group, header.bump, program_id)?;
+    Ok(header)
+}
+
+fn check_and_read_spending_limit(
+    info: &AccountInfo,
+    program_id: &Pubkey,
+) -> Result<SpendingLimit, Error> {
+    if info.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId.into());
+    }
+
+    let limit = read_account_data::<SpendingLimit>(AccountType::SpendingLimit, info)?;
+
+    let addr = Pubkey::create_program_address(
+        &[
+            pda_tag::SPENDING_LIMIT,
+            limit.group.as_ref(),
+            &limit.index.to_le_bytes(),
+            &[limit.bump],
+        ],
+        program_id,
+    )
+    .map_err(|_| Error::InvalidSpendingLimitAccountKey)?;
+    if addr != *info.key {
+        return Err(Error::InvalidSpendingLimitAccountKey);
+    }
+    Ok(limit)
+}
 
-    // TODO: Partial deserialize?
-    let serialized_instruction = proposal_data
-        .config
-        .try_to_vec()
-        .map_err(Error::Serialize)?;
-    let proposal_hash = hash(&serialized_instruction);
+/// Bump of the proposal key. Proposals of the first program release don't store it, so it is
+/// found from the config hash seeding their keys.
+fn proposal_bump(info: &AccountInfo, seed: &ProposalSeed, bump: u8, program_id: &Pubkey) -> u8 {
+    match seed {
+        ProposalSeed::Config(hash) if is_unversioned(&info.data.borrow()) => {
+            Pubkey::find_program_address(&[pda_tag::PROPOSAL, hash.as_ref()], program_id).1
+        }
+        _ => bump,
+    }
+}
 
-    let (addr, _) =
-        Pubkey::find_program_address(&[pda_tag::PROPOSAL, proposal_hash.as_ref()], program_id);
+fn check_proposal_key(
+    info: &AccountInfo,
+    seed: &ProposalSeed,
+    group_key: &Pubkey,
+    bump: u8,
+    program_id: &Pubkey,
+) -> Result<(), Error> {
+    let addr = match seed {
+        ProposalSeed::Config(hash) => {
+            Pubkey::create_program_address(&[pda_tag::PROPOSAL, hash.as_ref(), &[bump]], program_id)
+        }
+        ProposalSeed::Index(index) => Pubkey::create_program_address(
+            &[
+                pda_tag::PROPOSAL,
+                group_key.as_ref(),
+                &index.to_le_bytes(),
+                &[bump],
+            ],
+            program_id,
+        ),
+    }
+    .map_err(|_| Error::InvalidProposalAccountKey)?;
     if addr != *info.key {
         return Err(Error::InvalidProposalAccountKey);
     }
+    Ok(())
+}
 
-    Ok(proposal_data)
+/// Write account data in the current layout. Account too small for it is grown, and its rent
+/// exemption is topped up by the funding account.
+fn rewrite_account<T: Versioned>(
+    accounts: &[AccountInfo],
+    info: &AccountInfo,
+    funder_info: Option<&AccountInfo>,
+    account_type: AccountType,
+    data: &T,
+) -> Result<(), Error> {
+    let serialized_data = data.try_to_vec().map_err(Error::Serialize)?;
+    let len = serialized_data.len() + ACCOUNT_HEADER_LEN;
+    if info.data_len() < len {
+        let lamports = Rent::get()?
+            .minimum_balance(len)
+            .saturating_sub(info.lamports());
+        if lamports > 0 {
+            let funder_info = funder_info.ok_or(ProgramError::NotEnoughAccountKeys)?;
+            invoke(
+                &system_instruction::transfer(funder_info.key, info.key, lamports),
+                accounts,
+            )?;
+        }
+        grow_account_data(info, len)?;
+    }
+    write_serialized_data(info, account_type, T::VERSION, &serialized_data)
 }
 
-/// Transfer lamports back to a destination account.
-/// This happens when a proposal is closed.
-fn transfer_lamports_from_proposal(
+/// Create the next proposal account of the group, seeded by its index, and write the data into it.
+#[allow(clippy::too_many_arguments)]
+fn create_proposal_account(
+    accounts: &[AccountInfo],
+    program_id: &Pubkey,
+    funder_key: &Pubkey,
+    group_account_info: &AccountInfo,
+    group: &mut GroupAccount,
     proposal_account_info: &AccountInfo,
-    destination_account_info: &AccountInfo,
-) {
-    let lamports = **proposal_account_info.lamports.borrow();
-    msg!(
-        "transferring {} lamports to close the proposal account",
-        lamports
+    config: ProposalConfig,
+    state: ProposalState,
+    threshold: u32,
+    lamports: u64,
+    space: u64,
+) -> Result<(), Error> {
+    let index = group.proposal_count;
+    let index_bytes = index.to_le_bytes();
+    let (addr, bump) = Pubkey::find_program_address(
+        &[
+            pda_tag::PROPOSAL,
+            group_account_info.key.as_ref(),
+            &index_bytes,
+        ],
+        program_id,
+    );
+
+    if *proposal_account_info.key != addr {
+        return Err(Error::InvalidProposalAccountKey);
+    }
+    group.proposal_count += 1;
+    write_account_data(group_account_info, AccountType::Group, group)?;
+
+    let proposal = ProposalData {
+        seed: ProposalSeed::Index(index),
+        bump,
+        config,
+        state,
+        threshold,
+        group_version: group.config_version,
+        approval_nonce: 0,
+    };
+    let serialized_data = proposal.try_to_vec().map_err(Error::Serialize)?;
+
+    let create_instruction = create_account(
+        funder_key,
+        &addr,
+        lamports,
+        space.max((serialized_data.len() + ACCOUNT_HEADER_LEN) as u64),
+        program_id,
     );
-    **proposal_account_info.lamports.borrow_mut() = 0;
+
+    invoke_signed(
+        &create_instruction,
+        accounts,
+        &[&[
+            pda_tag::PROPOSAL,
+            group_account_info.key.as_ref(),
+            &index_bytes,
+            &[bump],
+        ]],
+    )?;
+
+    write_serialized_data(
+        proposal_account_info,
+        AccountType::Proposal,
+        ProposalData::VERSION,
+        &serialized_data,
+    )
+}
+
+/// Invoke proposed instructions on behalf of the group, signed by the vaults each of them names.
+fn invoke_proposed_instructions(
+    instructions: Vec<ProposedInstruction>,
+    accounts: &[AccountInfo],
+    group_key: &Pubkey,
+    group: &GroupAccount,
+    program_id: &Pubkey,
+) -> Result<(), Error> {
+    for instruction in instructions {
+        let index_bytes: Vec<[u8; 2]> = instruction
+            .vaults
+            .iter()
+            .map(|index| index.to_le_bytes())
+            .collect();
+        let nonces = instruction
+            .vaults
+            .iter()
+            .map(|index| Ok([vault_address(group_key, group, *index, program_id)?.1]))
+            .collect::<Result<Vec<[u8; 1]>, Error>>()?;
+        let signer_seeds: Vec<Vec<&[u8]>> = instruction
+            .vaults
+            .iter()
+            .zip(&index_bytes)
+            .zip(&nonces)
+            .map(|((index, bytes), nonce)| {
+                let mut seeds = vault_seeds(group_key, *index, bytes);
+                seeds.push(nonce);
+                seeds
+            })
+            .collect();
+        let signer_seeds: Vec<&[&[u8]]> = signer_seeds.iter().map(Vec::as_slice).collect();
+
+        invoke_signed(&instruction.try_into()?, accounts, &signer_seeds)?;
+    }
+    Ok(())
+}
+
+/// Create the receipt account of a proposal that ended with `status`, before the proposal account
+/// is closed. The receipt is topped up to `lamports`, or to rent exemption if not given, by the
+/// funding account: either a system account signing the instruction or the proposal account.
+fn create_receipt(
+    accounts: &[AccountInfo],
+    program_id: &Pubkey,
+    proposal_account_info: &AccountInfo,
+    receipt_account_info: &AccountInfo,
+    funder_account_info: &AccountInfo,
+    lamports: Option<u64>,
+    status: ProposalStatus,
+) -> Result<(), Error> {
+    let proposal = read_account_data::<ProposalData>(AccountType::Proposal, proposal_account_info)?;
+    let (receipt_key, bump) = Pubkey::find_program_address(
+        &[pda_tag::RECEIPT, proposal_account_info.key.as_ref()],
+        program_id,
+    );
+    if *receipt_account_info.key != receipt_key {
+        return Err(Error::InvalidReceiptAccountKey);
+    }
+
+    let clock = Clock::get()?;
+    let receipt = ProposalReceipt {
+        group: proposal.config.group,
+        proposal: *proposal_account_info.key,
+        bump,
+        config_hash: proposal.config.seed()?,
+        status,
+        slot: clock.slot,
+        unix_timestamp: clock.unix_timestamp,
+    };
+    let serialized_data = receipt.try_to_vec().map_err(Error::Serialize)?;
+    let space = serialized_data.len() + ACCOUNT_HEADER_LEN;
+
+    // Receipt address is known in advance and anyone can send lamports to it, which would make
+    // create_account fail. Top it up, allocate and assign instead.
+    let lamports = match lamports {
+        Some(lamports) => lamports,
+        None => Rent::get()?.minimum_balance(space),
+    }
+    .saturating_sub(receipt_account_info.lamports());
+    if funder_account_info.owner == program_id {
+        // The program owns the proposal account, so the system program can't debit it.
+        let funder_lamports = funder_account_info
+            .lamports()
+            .checked_sub(lamports)
+            .ok_or(ProgramError::InsufficientFunds)?;
+        **funder_account_info.lamports.borrow_mut() = funder_lamports;
+        **receipt_account_info.lamports.borrow_mut() += lamports;
+    } else if lamports > 0 {
+        invoke(
+            &system_instruction::transfer(funder_account_info.key, &receipt_key, lamports),
+            accounts,
+        )?;
+    }
+    let signer_seeds: &[&[u8]] = &[
+        pda_tag::RECEIPT,
+        proposal_account_info.key.as_ref(),
+        &[bump],
+    ];
+    invoke_signed(
+        &system_instruction::allocate(&receipt_key, space as u64),
+        accounts,
+        &[signer_seeds],
+    )?;
+    invoke_signed(
+        &system_instruction::assign(&receipt_key, program_id),
+        accounts,
+        &[signer_seeds],
+    )?;
+    write_serialized_data(
+        receipt_account_info,
+        AccountType::Receipt,
+        ProposalReceipt::VERSION,
+        &serialized_data,
+    )?;
+    Event::ReceiptCreated {
+        group: receipt.group,
+        proposal: receipt.proposal,
+        receipt: receipt_key,
+    }
+    .emit()
+}
+
+/// Zero proposal data and transfer its lamports to a destination account.
+fn close_proposal_account(
+    proposal_account_info: &AccountInfo,
+    destination_account_info: &AccountInfo,
+) -> Result<(), Error> {
+    let lamports = close_account(proposal_account_info, destination_account_info);
+    Event::Closed {
+        proposal: *proposal_account_info.key,
+        destination: *destination_account_info.key,
+        lamports,
+    }
+    .emit()
+}
+
+/// Zero data of a program account and transfer all its lamports to a destination account.
+/// Returns the amount transferred.
+fn close_account(account_info: &AccountInfo, destination_account_info: &AccountInfo) -> u64 {
+    for i in &mut **account_info.data.borrow_mut() {
+        *i = 0;
+    }
+
+    let lamports = **account_info.lamports.borrow();
+    **account_info.lamports.borrow_mut() = 0;
     **destination_account_info.lamports.borrow_mut() += lamports;
+    lamports
 }
@@ -1,19 +1,33 @@
 use crate::error::Error;
 use std::convert::TryFrom;
+use std::fmt;
+use std::io::{self, Write};
 
 use solana_program::{
+    clock::Clock,
+    hash::{hash, Hash},
     instruction::{AccountMeta, Instruction},
     pubkey::Pubkey,
 };
 
 use borsh::{BorshDeserialize, BorshSerialize};
 
+/// Account type tag, the first byte of account data.
+///
+/// The first program release stored group and proposal accounts with tags 1 and 2 and no layout
+/// version. Their versioned layouts have tags of their own, so the two can't be mixed up.
 #[repr(u8)]
 pub enum AccountType {
-    Group = 1,
-    Proposal = 2,
+    SpendingLimit = 3,
+    Receipt = 4,
+    Group = 5,
+    Proposal = 6,
 }
 
+/// Tags of unversioned group and proposal accounts of the first program release.
+const UNVERSIONED_GROUP: u8 = 1;
+const UNVERSIONED_PROPOSAL: u8 = 2;
+
 impl From<AccountType> for u8 {
     #[inline]
     fn from(value: AccountType) -> Self {
@@ -21,36 +35,355 @@ impl From<AccountType> for u8 {
     }
 }
 
+impl TryFrom<u8> for AccountType {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            UNVERSIONED_GROUP | 5 => Ok(AccountType::Group),
+            UNVERSIONED_PROPOSAL | 6 => Ok(AccountType::Proposal),
+            3 => Ok(AccountType::SpendingLimit),
+            4 => Ok(AccountType::Receipt),
+            _ => Err(Error::InvalidAccountType),
+        }
+    }
+}
+
+impl AccountType {
+    /// Tag the account type had in the first program release, if it existed back then.
+    fn unversioned_tag(&self) -> Option<u8> {
+        match self {
+            AccountType::Group => Some(UNVERSIONED_GROUP),
+            AccountType::Proposal => Some(UNVERSIONED_PROPOSAL),
+            AccountType::SpendingLimit | AccountType::Receipt => None,
+        }
+    }
+}
+
+/// Whether account data is a group or proposal account of the first program release. These
+/// don't store the bumps of their keys.
+pub fn is_unversioned(data: &[u8]) -> bool {
+    matches!(
+        data.first(),
+        Some(&UNVERSIONED_GROUP) | Some(&UNVERSIONED_PROPOSAL)
+    )
+}
+
+/// Bytes preceding account data: account type and layout version.
+pub const ACCOUNT_HEADER_LEN: usize = 2;
+
+/// Account data stored along with its layout version, so accounts written by older program
+/// versions stay readable after the layout changes.
+pub trait Versioned: BorshSerialize + Sized {
+    /// Layout version the data is written with.
+    const VERSION: u8;
+
+    /// Decode data written with layout `version`, which is at most [Self::VERSION].
+    fn deserialize_version(version: u8, buf: &mut &[u8]) -> io::Result<Self>;
+
+    /// Decode data of the first program release, written with no layout version.
+    fn deserialize_unversioned(_buf: &mut &[u8]) -> io::Result<Self> {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "no unversioned layout",
+        ))
+    }
+}
+
+/// Decode account data of the given type, written with the current or an older layout version.
+pub fn unpack_account_data<T: Versioned>(
+    account_type: AccountType,
+    data: &[u8],
+) -> Result<T, Error> {
+    let (&tag, mut data) = data.split_first().ok_or(Error::EmptyAccountData)?;
+    if Some(tag) == account_type.unversioned_tag() {
+        return T::deserialize_unversioned(&mut data).map_err(Error::AccountDataDeserialize);
+    }
+    if tag != u8::from(account_type) {
+        return Err(Error::InvalidAccountType);
+    }
+    let (&version, mut data) = data.split_first().ok_or(Error::EmptyAccountData)?;
+    if version == 0 || version > T::VERSION {
+        return Err(Error::UnsupportedAccountVersion);
+    }
+    // Account may have more space than data needs, so trailing bytes are not an error.
+    T::deserialize_version(version, &mut data).map_err(Error::AccountDataDeserialize)
+}
+
+fn unknown_version(version: u8) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("unknown layout version {}", version),
+    )
+}
+
+/// Group account contents.
 #[derive(Debug, BorshSerialize, BorshDeserialize)]
+pub struct GroupAccount {
+    /// Hash of the group data the group was initialized with. Seeds the group account key,
+    /// so the key (and the protected account derived from it) survives group updates.
+    pub seed: Hash,
+    /// Bump of the group account key.
+    pub bump: u8,
+    /// Bumps of the group vaults created by the program, starting with the protected account.
+    pub vaults: Vec<GroupVault>,
+    pub data: GroupData,
+    /// Number of proposal accounts created for the group. Index of the next proposal,
+    /// which seeds its account key.
+    pub proposal_count: u64,
+    /// Incremented by every group update. Proposals record the version they were created
+    /// for, as their votes refer to members by index.
+    pub config_version: u64,
+}
+
+#[derive(Debug, BorshSerialize, BorshDeserialize, Clone, Copy, PartialEq)]
+pub struct GroupVault {
+    pub index: u16,
+    pub bump: u8,
+}
+
+#[derive(Debug, BorshSerialize, BorshDeserialize, Clone)]
 pub struct GroupData {
     pub members: Vec<GroupMember>,
     pub threshold: u32,
+    /// Seconds to wait after a proposal reaches the threshold before it can be executed.
+    /// Zero means [Execute](crate::instruction::ExecuteInstruction) is allowed as soon as the
+    /// proposal is approved.
+    pub execution_delay: u64,
+    /// Thresholds for instructions of particular programs, replacing `threshold` for them.
+    pub threshold_rules: Vec<ThresholdRule>,
+    /// Programs proposed instructions may invoke.
+    pub program_policy: ProgramPolicy,
 }
 
-#[derive(Debug, BorshSerialize, BorshDeserialize)]
+/// Programs the group vaults may sign instructions for. Instructions of the multisig program
+/// itself are always allowed, so the policy can be changed by a group update proposal.
+#[derive(Debug, BorshSerialize, BorshDeserialize, Clone, PartialEq)]
+pub enum ProgramPolicy {
+    /// Any program may be invoked.
+    Any,
+    /// Only the listed programs may be invoked.
+    Allow(Vec<Pubkey>),
+    /// Any program except the listed ones may be invoked.
+    Deny(Vec<Pubkey>),
+}
+
+/// Threshold for proposed instructions invoking a program. A proposal needs the highest
+/// threshold among its instructions. Rules can't lower the threshold of instructions invoking
+/// the multisig program itself.
+#[derive(Debug, BorshSerialize, BorshDeserialize, Clone, PartialEq)]
+pub struct ThresholdRule {
+    pub program_id: Pubkey,
+    /// The rule applies only to instructions with data starting with the prefix.
+    /// Empty prefix matches any instruction of the program.
+    pub data_prefix: Vec<u8>,
+    pub threshold: u32,
+}
+
+#[derive(Debug, BorshSerialize, BorshDeserialize, Clone)]
 pub struct GroupMember {
+    /// Member signing key. Can be the protected account of another group, which signs
+    /// through the proposals it executes.
     pub public_key: Pubkey,
     pub weight: u32,
 }
 
-#[derive(Debug, BorshSerialize, BorshDeserialize)]
+/// Group data of the first program release, stored with no layout version.
+#[derive(BorshDeserialize)]
+struct UnversionedGroupData {
+    members: Vec<GroupMember>,
+    threshold: u32,
+}
+
+/// Proposal config of the first program release, followed by its votes.
+#[derive(BorshDeserialize)]
+struct UnversionedProposalConfig {
+    group: Pubkey,
+    instructions: Vec<UnversionedInstruction>,
+    author: Pubkey,
+    salt: u64,
+}
+
+#[derive(BorshDeserialize)]
+struct UnversionedInstruction {
+    program_id: Pubkey,
+    accounts: Vec<ProposedAccountMeta>,
+    data: Vec<u8>,
+}
+
+/// Votes on a proposal. Fixed size, so it can be kept in the proposal header.
+#[derive(Debug, BorshSerialize, BorshDeserialize, Clone, PartialEq)]
 pub struct ProposalState {
-    members: u64,
+    status: ProposalStatus,
+    members: MemberSet,
+    rejected_members: MemberSet,
     current_weight: u32,
+    rejected_weight: u32,
+    /// Unix timestamp of the moment the proposal reached the threshold.
+    /// Meaningful only for approved and executed proposals.
+    approved_at: i64,
 }
 
-#[derive(Debug, BorshSerialize, BorshDeserialize)]
+/// Set of group member indices, one bit per member. Holds up to [GroupData::MAX_MEMBERS]
+/// members, indices past the member count the set was created with are out of range.
+#[derive(Debug, BorshSerialize, BorshDeserialize, Clone, PartialEq)]
+pub struct MemberSet {
+    len: u16,
+    bits: [u8; GroupData::MAX_MEMBERS / 8],
+}
+
+/// Rejected, cancelled and expired proposals are closed, so those statuses are only stored
+/// in receipts.
+#[derive(Debug, BorshSerialize, BorshDeserialize, Clone, Copy, PartialEq)]
+pub enum ProposalStatus {
+    /// Proposal is collecting approvals.
+    Active,
+    /// Proposal reached the threshold and can be executed.
+    Approved,
+    /// Proposed instructions were executed.
+    Executed,
+    /// Proposal author is still adding instructions, proposal can't be voted on yet.
+    Draft,
+    /// Rejecting members made the threshold unreachable.
+    Rejected,
+    /// Proposal author closed the proposal before it was executed.
+    Cancelled,
+    /// Proposal expired before it was executed.
+    Expired,
+}
+
+#[derive(Debug, BorshSerialize, BorshDeserialize, Clone)]
 pub struct ProposalConfig {
     pub group: Pubkey,
     pub instructions: Vec<ProposedInstruction>,
     pub author: Pubkey,
-    pub salt: u64
+    pub salt: u64,
+    pub expiry: Option<ProposalExpiry>,
 }
 
-#[derive(Debug, BorshSerialize, BorshDeserialize)]
+/// Point in time after which a proposal can no longer be approved.
+#[derive(Debug, BorshSerialize, BorshDeserialize, Clone, Copy, PartialEq)]
+pub enum ProposalExpiry {
+    /// Proposal expires once the cluster reaches this slot.
+    Slot(u64),
+    /// Proposal expires once the cluster Unix timestamp reaches this value.
+    UnixTimestamp(i64),
+}
+
+/// Message a member signs off-chain to approve a proposal, see
+/// [ApproveWithSignaturesInstruction](crate::instruction::ApproveWithSignaturesInstruction).
+/// Signed as [ApprovalMessage::PREFIX] followed by the Borsh encoding of the message,
+/// and shown to the signer as its [Display](fmt::Display) rendering.
+#[derive(Debug, BorshSerialize, BorshDeserialize, Clone, PartialEq)]
+pub struct ApprovalMessage {
+    /// Message format version, [ApprovalMessage::VERSION] for messages created by this program.
+    pub version: u8,
+    pub program_id: Pubkey,
+    pub group: Pubkey,
+    pub proposal: Pubkey,
+    /// Hash of the proposal config, covering the proposed instructions.
+    pub config_hash: Hash,
+    pub expiry: Option<ProposalExpiry>,
+    /// [ProposalHeader::approval_nonce] at signing. Signatures stop counting once any approval
+    /// is revoked, so they have to be collected again.
+    pub approval_nonce: u64,
+}
+
+/// What the key of a proposal account is derived from.
+#[derive(Debug, BorshSerialize, BorshDeserialize, Clone, Copy, PartialEq)]
+pub enum ProposalSeed {
+    /// Hash of the config the proposal was created with, for proposals of the first program
+    /// release.
+    Config(Hash),
+    /// Index of the proposal among the group proposals.
+    Index(u64),
+}
+
+/// Proposal account contents. Stored as [ProposalHeader] followed by the proposed instructions.
+#[derive(Debug)]
 pub struct ProposalData {
+    /// Seeds the proposal account key.
+    pub seed: ProposalSeed,
+    /// Bump of the proposal account key.
+    pub bump: u8,
     pub config: ProposalConfig,
     pub state: ProposalState,
+    /// Threshold of the proposal instructions, see [ProposalHeader::threshold].
+    pub threshold: u32,
+    /// Group config version, see [ProposalHeader::group_version].
+    pub group_version: u64,
+    /// Revoked approval count, see [ProposalHeader::approval_nonce].
+    pub approval_nonce: u64,
+}
+
+/// Fixed-size head of a proposal account: everything but the proposed instructions.
+/// Votes read and write only the header, so they cost the same for proposals of any size.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProposalHeader {
+    pub state: ProposalState,
+    pub bump: u8,
+    pub seed: ProposalSeed,
+    pub group: Pubkey,
+    pub author: Pubkey,
+    pub salt: u64,
+    pub expiry: Option<ProposalExpiry>,
+    /// Threshold of the proposal instructions under the group threshold rules, set when the
+    /// instructions are fixed. Zero for proposals of the first program release, which need
+    /// the group threshold.
+    pub threshold: u32,
+    /// [GroupAccount::config_version] the proposal was created for. Votes and execution
+    /// are refused once the group is updated. Zero for proposals of the first program release,
+    /// like the version of groups of that release.
+    pub group_version: u64,
+    /// Number of approvals revoked from the proposal. Signed approvals cover it, so a signature
+    /// made before a revocation can't restore the revoked approval.
+    pub approval_nonce: u64,
+}
+
+/// Spending limit account contents. Lets members move funds from a vault without a proposal.
+#[derive(Debug, BorshSerialize, BorshDeserialize)]
+pub struct SpendingLimit {
+    pub group: Pubkey,
+    /// Index of the limit among the group limits. Seeds the spending limit account key.
+    pub index: u16,
+    /// Bump of the spending limit account key.
+    pub bump: u8,
+    pub config: SpendingLimitConfig,
+    /// Unix timestamp the current period started at.
+    pub period_start: i64,
+    /// Amount spent in the current period.
+    pub spent: u64,
+}
+
+/// Receipt account contents. Records how a proposal ended after its account is closed: executed,
+/// rejected, cancelled or expired.
+#[derive(Debug, BorshSerialize, BorshDeserialize, Clone, PartialEq)]
+pub struct ProposalReceipt {
+    pub group: Pubkey,
+    /// Key of the closed proposal account. Seeds the receipt account key.
+    pub proposal: Pubkey,
+    /// Bump of the receipt account key.
+    pub bump: u8,
+    /// Hash of the proposal config, covering the executed instructions.
+    pub config_hash: Hash,
+    pub status: ProposalStatus,
+    pub slot: u64,
+    pub unix_timestamp: i64,
+}
+
+/// What a spending limit allows. Set by the group when the limit is created and never changed.
+#[derive(Debug, BorshSerialize, BorshDeserialize, Clone, PartialEq)]
+pub struct SpendingLimitConfig {
+    /// Index of the group vault funds are spent from.
+    pub vault: u16,
+    /// Mint of the tokens that can be spent, `None` for lamports.
+    pub mint: Option<Pubkey>,
+    /// Amount that can be spent per period.
+    pub amount: u64,
+    /// Period length in seconds.
+    pub period: i64,
+    /// Members allowed to spend. Empty means any group member.
+    pub members: Vec<Pubkey>,
 }
 
 #[derive(Debug, BorshSerialize, BorshDeserialize, Clone)]
@@ -58,6 +391,8 @@ pub struct ProposedInstruction {
     pub program_id: Pubkey,
     pub accounts: Vec<ProposedAccountMeta>,
     pub data: Vec<u8>,
+    /// Indices of group vaults that sign the instruction.
+    pub vaults: Vec<u16>,
 }
 
 #[derive(Debug, BorshSerialize, BorshDeserialize, Clone)]
@@ -68,6 +403,104 @@ pub struct ProposedAccountMeta {
 }
 
 impl GroupData {
+    /// Maximum number of members. Keeps group account within the size
+    /// a program can allocate when creating an account.
+    pub const MAX_MEMBERS: usize = 256;
+
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.threshold == 0 {
+            return Err(Error::ZeroThreshold);
+        }
+        if self.members.len() > Self::MAX_MEMBERS {
+            return Err(Error::TooManyMembers);
+        }
+        if self.members.is_empty() {
+            return Err(Error::NoMembers);
+        }
+        for member in &self.members {
+            if member.weight == 0 {
+                return Err(Error::ZeroWeight);
+            }
+        }
+        if self.total_weight() < self.threshold {
+            return Err(Error::UnreachableThreshold);
+        }
+        for rule in &self.threshold_rules {
+            if rule.threshold == 0 {
+                return Err(Error::ZeroThreshold);
+            }
+            if self.total_weight() < rule.threshold {
+                return Err(Error::UnreachableThreshold);
+            }
+        }
+        // Delay is added to unix timestamps.
+        if i64::try_from(self.execution_delay).is_err() {
+            return Err(Error::InvalidExecutionDelay);
+        }
+        Ok(())
+    }
+
+    /// Threshold of a proposal with the given instructions: the highest threshold among them.
+    /// Instructions matched by no rule need the group threshold. Instructions invoking the
+    /// multisig program itself can change the group and its rules, so they need the highest
+    /// threshold of the group and all its rules.
+    pub fn proposal_threshold(
+        &self,
+        instructions: &[ProposedInstruction],
+        multisig_program_id: &Pubkey,
+    ) -> u32 {
+        instructions
+            .iter()
+            .map(|instruction| {
+                if instruction.program_id == *multisig_program_id {
+                    return self
+                        .threshold_rules
+                        .iter()
+                        .map(|rule| rule.threshold)
+                        .fold(self.threshold, u32::max);
+                }
+                self.threshold_rules
+                    .iter()
+                    .filter(|rule| {
+                        rule.program_id == instruction.program_id
+                            && instruction.data.starts_with(&rule.data_prefix)
+                    })
+                    .map(|rule| rule.threshold)
+                    .max()
+                    .unwrap_or(self.threshold)
+            })
+            .max()
+            .unwrap_or(self.threshold)
+    }
+
+    /// Check that the group policy allows invoking programs of the proposed instructions.
+    pub fn check_programs(
+        &self,
+        instructions: &[ProposedInstruction],
+        multisig_program_id: &Pubkey,
+    ) -> Result<(), Error> {
+        for instruction in instructions {
+            let program_id = &instruction.program_id;
+            let allowed = program_id == multisig_program_id
+                || match &self.program_policy {
+                    ProgramPolicy::Any => true,
+                    ProgramPolicy::Allow(programs) => programs.contains(program_id),
+                    ProgramPolicy::Deny(programs) => !programs.contains(program_id),
+                };
+            if !allowed {
+                return Err(Error::ProgramNotAllowed);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn total_weight(&self) -> u32 {
+        self.members
+            .iter()
+            .map(|m| m.weight)
+            .fold(0, |sum, weight| sum.saturating_add(weight))
+    }
+
     pub fn weight(&self, key: &Pubkey) -> Result<(usize, u32), Error> {
         self.members
             .iter()
@@ -78,32 +511,546 @@ impl GroupData {
     }
 }
 
+impl ProposalHeader {
+    /// Serialized size of the header.
+    pub const LEN: usize = ProposalState::LEN + 1 + SEED_LEN + 32 + 32 + 8 + EXPIRY_LEN + 4 + 8 + 8;
+
+    /// Threshold votes are counted against.
+    pub fn threshold(&self, group: &GroupData) -> u32 {
+        if self.threshold == 0 {
+            group.threshold
+        } else {
+            self.threshold
+        }
+    }
+
+    pub fn is_expired(&self, clock: &Clock) -> bool {
+        self.expiry
+            .map(|expiry| expiry.is_expired(clock))
+            .unwrap_or(false)
+    }
+}
+
+/// Serialized size of `Option<ProposalExpiry>` padded to its largest variant.
+const EXPIRY_LEN: usize = 1 + 1 + 8;
+/// Serialized size of the largest [ProposalSeed].
+const SEED_LEN: usize = 1 + 32;
+
+impl BorshSerialize for ProposalHeader {
+    fn serialize<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.state.serialize(writer)?;
+        self.bump.serialize(writer)?;
+        // Seeds and expiries differ in size, pad them so the header keeps its size.
+        let seed = self.seed.try_to_vec()?;
+        writer.write_all(&seed)?;
+        writer.write_all(&[0; SEED_LEN][seed.len()..])?;
+        self.group.serialize(writer)?;
+        self.author.serialize(writer)?;
+        self.salt.serialize(writer)?;
+        let expiry = self.expiry.try_to_vec()?;
+        writer.write_all(&expiry)?;
+        writer.write_all(&[0; EXPIRY_LEN][expiry.len()..])?;
+        self.threshold.serialize(writer)?;
+        self.group_version.serialize(writer)?;
+        self.approval_nonce.serialize(writer)
+    }
+}
+
+impl BorshDeserialize for ProposalHeader {
+    fn deserialize(buf: &mut &[u8]) -> io::Result<Self> {
+        Ok(Self {
+            state: ProposalState::deserialize(buf)?,
+            bump: u8::deserialize(buf)?,
+            seed: deserialize_padded(buf, SEED_LEN)?,
+            group: Pubkey::deserialize(buf)?,
+            author: Pubkey::deserialize(buf)?,
+            salt: u64::deserialize(buf)?,
+            expiry: deserialize_padded(buf, EXPIRY_LEN)?,
+            threshold: u32::deserialize(buf)?,
+            group_version: u64::deserialize(buf)?,
+            approval_nonce: u64::deserialize(buf)?,
+        })
+    }
+}
+
+/// Decode a value padded to `len` bytes.
+fn deserialize_padded<T: BorshDeserialize>(buf: &mut &[u8], len: usize) -> io::Result<T> {
+    if buf.len() < len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    let (mut value, rest) = buf.split_at(len);
+    let value = T::deserialize(&mut value)?;
+    *buf = rest;
+    Ok(value)
+}
+
+impl Versioned for GroupAccount {
+    const VERSION: u8 = 1;
+
+    fn deserialize_version(version: u8, buf: &mut &[u8]) -> io::Result<Self> {
+        match version {
+            1 => Self::deserialize(buf),
+            _ => Err(unknown_version(version)),
+        }
+    }
+
+    /// Group key is seeded by the hash of the stored data. The bump is not stored, so it is left
+    /// zero for the caller to find.
+    fn deserialize_unversioned(buf: &mut &[u8]) -> io::Result<Self> {
+        let start = *buf;
+        let data = UnversionedGroupData::deserialize(buf)?;
+        Ok(Self {
+            seed: hash(&start[..start.len() - buf.len()]),
+            bump: 0,
+            vaults: Vec::new(),
+            data: GroupData {
+                members: data.members,
+                threshold: data.threshold,
+                execution_delay: 0,
+                threshold_rules: Vec::new(),
+                program_policy: ProgramPolicy::Any,
+            },
+            proposal_count: 0,
+            config_version: 0,
+        })
+    }
+}
+
+impl Versioned for ProposalHeader {
+    const VERSION: u8 = 1;
+
+    fn deserialize_version(version: u8, buf: &mut &[u8]) -> io::Result<Self> {
+        match version {
+            1 => Self::deserialize(buf),
+            _ => Err(unknown_version(version)),
+        }
+    }
+
+    /// Unversioned proposals keep votes after the instructions, so the whole proposal is read.
+    fn deserialize_unversioned(buf: &mut &[u8]) -> io::Result<Self> {
+        ProposalData::deserialize_unversioned(buf).map(|proposal| proposal.header())
+    }
+}
+
+impl Versioned for ProposalData {
+    /// Proposal header and data always share the layout version.
+    const VERSION: u8 = ProposalHeader::VERSION;
+
+    fn deserialize_version(version: u8, buf: &mut &[u8]) -> io::Result<Self> {
+        let header = ProposalHeader::deserialize_version(version, buf)?;
+        let instructions = Vec::<ProposedInstruction>::deserialize(buf)?;
+        Ok(Self::from_parts(header, instructions))
+    }
+
+    /// Proposal key is seeded by the hash of the stored config. The bump is not stored, so it is
+    /// left zero for the caller to find.
+    fn deserialize_unversioned(buf: &mut &[u8]) -> io::Result<Self> {
+        let start = *buf;
+        let config = UnversionedProposalConfig::deserialize(buf)?;
+        let seed = hash(&start[..start.len() - buf.len()]);
+        let members = u64::deserialize(buf)?;
+        let current_weight = u32::deserialize(buf)?;
+        Ok(Self {
+            seed: ProposalSeed::Config(seed),
+            bump: 0,
+            config: ProposalConfig {
+                group: config.group,
+                instructions: config
+                    .instructions
+                    .into_iter()
+                    .map(|instruction| ProposedInstruction {
+                        program_id: instruction.program_id,
+                        accounts: instruction.accounts,
+                        data: instruction.data,
+                        // Instructions were signed by the protected account.
+                        vaults: vec![0],
+                    })
+                    .collect(),
+                author: config.author,
+                salt: config.salt,
+                expiry: None,
+            },
+            state: ProposalState::from_unversioned(members, current_weight),
+            threshold: 0,
+            group_version: 0,
+            approval_nonce: 0,
+        })
+    }
+}
+
+impl Versioned for SpendingLimit {
+    const VERSION: u8 = 1;
+
+    fn deserialize_version(version: u8, buf: &mut &[u8]) -> io::Result<Self> {
+        match version {
+            1 => Self::deserialize(buf),
+            _ => Err(unknown_version(version)),
+        }
+    }
+}
+
+impl ProposalReceipt {
+    /// Serialized size of the receipt.
+    pub const LEN: usize = 32 + 32 + 1 + 32 + 1 + 8 + 8;
+}
+
+impl Versioned for ProposalReceipt {
+    const VERSION: u8 = 1;
+
+    fn deserialize_version(version: u8, buf: &mut &[u8]) -> io::Result<Self> {
+        match version {
+            1 => Self::deserialize(buf),
+            _ => Err(unknown_version(version)),
+        }
+    }
+}
+
+impl ProposalData {
+    /// Threshold votes are counted against, see [ProposalHeader::threshold].
+    pub fn threshold(&self, group: &GroupData) -> u32 {
+        self.header().threshold(group)
+    }
+
+    pub fn header(&self) -> ProposalHeader {
+        ProposalHeader {
+            state: self.state.clone(),
+            bump: self.bump,
+            seed: self.seed,
+            group: self.config.group,
+            author: self.config.author,
+            salt: self.config.salt,
+            expiry: self.config.expiry,
+            threshold: self.threshold,
+            group_version: self.group_version,
+            approval_nonce: self.approval_nonce,
+        }
+    }
+
+    fn from_parts(header: ProposalHeader, instructions: Vec<ProposedInstruction>) -> Self {
+        Self {
+            seed: header.seed,
+            bump: header.bump,
+            config: ProposalConfig {
+                group: header.group,
+                instructions,
+                author: header.author,
+                salt: header.salt,
+                expiry: header.expiry,
+            },
+            state: header.state,
+            threshold: header.threshold,
+            group_version: header.group_version,
+            approval_nonce: header.approval_nonce,
+        }
+    }
+}
+
+impl BorshSerialize for ProposalData {
+    fn serialize<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.header().serialize(writer)?;
+        self.config.instructions.serialize(writer)
+    }
+}
+
+impl BorshDeserialize for ProposalData {
+    fn deserialize(buf: &mut &[u8]) -> io::Result<Self> {
+        Self::deserialize_version(Self::VERSION, buf)
+    }
+}
+
+impl GroupAccount {
+    /// Bump of the group vault with the given index, if the vault was created by the program.
+    pub fn vault_bump(&self, index: u16) -> Option<u8> {
+        self.vaults
+            .iter()
+            .find(|vault| vault.index == index)
+            .map(|vault| vault.bump)
+    }
+
+    /// Check that a proposal created for group config `version` is still valid. Its votes refer
+    /// to members by index, which a group update may have changed.
+    pub fn check_proposal_version(&self, version: u64) -> Result<(), Error> {
+        if version != self.config_version {
+            return Err(Error::StaleProposal);
+        }
+        Ok(())
+    }
+}
+
+impl SpendingLimitConfig {
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.period <= 0 {
+            return Err(Error::InvalidSpendingLimitPeriod);
+        }
+        Ok(())
+    }
+
+    /// Whether the member may spend within the limit.
+    pub fn allows(&self, member: &Pubkey) -> bool {
+        self.members.is_empty() || self.members.contains(member)
+    }
+}
+
+impl SpendingLimit {
+    /// Record `amount` spent at `timestamp`, starting a new period if the current one is over.
+    pub fn spend(&mut self, amount: u64, timestamp: i64) -> Result<(), Error> {
+        if timestamp >= self.period_start.saturating_add(self.config.period) {
+            self.period_start = timestamp;
+            self.spent = 0;
+        }
+        self.spent = self
+            .spent
+            .checked_add(amount)
+            .filter(|spent| *spent <= self.config.amount)
+            .ok_or(Error::SpendingLimitExceeded)?;
+        Ok(())
+    }
+}
+
+impl ProposalExpiry {
+    pub fn is_expired(&self, clock: &Clock) -> bool {
+        match *self {
+            ProposalExpiry::Slot(slot) => clock.slot >= slot,
+            ProposalExpiry::UnixTimestamp(timestamp) => clock.unix_timestamp >= timestamp,
+        }
+    }
+}
+
+impl ProposalConfig {
+    /// Hash of the config. Seeds the key of a proposal account created with it.
+    pub fn seed(&self) -> Result<Hash, Error> {
+        Ok(hash(&self.try_to_vec().map_err(Error::Serialize)?))
+    }
+
+    pub fn is_expired(&self, clock: &Clock) -> bool {
+        self.expiry
+            .map(|expiry| expiry.is_expired(clock))
+            .unwrap_or(false)
+    }
+}
+
+impl ApprovalMessage {
+    pub const VERSION: u8 = 1;
+    /// Marks signed bytes as an approval message. Starts with a byte no transaction message
+    /// starts with, so a signature of one can't be replayed as the other.
+    pub const PREFIX: &'static [u8] = b"\xffsolana-multisig approval";
+
+    /// Message approving the proposal at `proposal` created with `config`, while its
+    /// approval nonce is `approval_nonce`.
+    pub fn new(
+        program_id: &Pubkey,
+        proposal: &Pubkey,
+        config: &ProposalConfig,
+        approval_nonce: u64,
+    ) -> Result<Self, Error> {
+        Ok(Self {
+            version: Self::VERSION,
+            program_id: *program_id,
+            group: config.group,
+            proposal: *proposal,
+            config_hash: config.seed()?,
+            expiry: config.expiry,
+            approval_nonce,
+        })
+    }
+
+    /// Bytes to sign.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        let mut bytes = Self::PREFIX.to_vec();
+        self.serialize(&mut bytes).map_err(Error::Serialize)?;
+        Ok(bytes)
+    }
+
+    /// Decode signed bytes, e.g. for a signer to show them before signing.
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        if !bytes.starts_with(Self::PREFIX) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not an approval message",
+            ));
+        }
+        let message = Self::try_from_slice(&bytes[Self::PREFIX.len()..])?;
+        if message.version != Self::VERSION {
+            return Err(unknown_version(message.version));
+        }
+        Ok(message)
+    }
+}
+
+impl fmt::Display for ApprovalMessage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Approve multisig proposal")?;
+        writeln!(f, "Version: {}", self.version)?;
+        writeln!(f, "Program: {}", self.program_id)?;
+        writeln!(f, "Group: {}", self.group)?;
+        writeln!(f, "Proposal: {}", self.proposal)?;
+        writeln!(f, "Config hash: {}", self.config_hash)?;
+        writeln!(f, "Approval nonce: {}", self.approval_nonce)?;
+        match self.expiry {
+            Some(ProposalExpiry::Slot(slot)) => write!(f, "Expires: at slot {}", slot),
+            Some(ProposalExpiry::UnixTimestamp(timestamp)) => {
+                write!(f, "Expires: at Unix timestamp {}", timestamp)
+            }
+            None => write!(f, "Expires: never"),
+        }
+    }
+}
+
+impl MemberSet {
+    /// Serialized size of the set.
+    pub const LEN: usize = 2 + GroupData::MAX_MEMBERS / 8;
+
+    pub fn new(member_count: usize) -> Self {
+        Self {
+            len: member_count.min(GroupData::MAX_MEMBERS) as u16,
+            bits: [0; GroupData::MAX_MEMBERS / 8],
+        }
+    }
+
+    pub fn contains(&self, idx: usize) -> bool {
+        idx < self.len as usize && self.bits[idx / 8] & (1 << (idx % 8)) != 0
+    }
+
+    pub fn insert(&mut self, idx: usize) -> Result<(), Error> {
+        if idx >= self.len as usize {
+            return Err(Error::InvalidMemberIndex);
+        }
+        self.bits[idx / 8] |= 1 << (idx % 8);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, idx: usize) {
+        if idx < self.len as usize {
+            self.bits[idx / 8] &= !(1 << (idx % 8));
+        }
+    }
+}
+
 impl ProposalState {
-    #[allow(clippy::new_without_default)]
-    pub fn new() -> Self {
+    /// Serialized size of the state.
+    pub const LEN: usize = 1 + 2 * MemberSet::LEN + 4 + 4 + 8;
+
+    pub fn new(member_count: usize) -> Self {
         Self {
+            status: ProposalStatus::Active,
+            members: MemberSet::new(member_count),
+            rejected_members: MemberSet::new(member_count),
             current_weight: 0,
-            members: 0,
+            rejected_weight: 0,
+            approved_at: 0,
+        }
+    }
+
+    /// Votes of a proposal of the first program release: members that approved as a bit mask
+    /// of up to 64 members, and their total weight. Such proposals were never approved, as they
+    /// were executed by the approval reaching the threshold.
+    fn from_unversioned(members: u64, current_weight: u32) -> Self {
+        let mut state = Self::new(64);
+        state.members.bits[..8].copy_from_slice(&members.to_le_bytes());
+        state.current_weight = current_weight;
+        state
+    }
+
+    pub fn new_draft(member_count: usize) -> Self {
+        Self {
+            status: ProposalStatus::Draft,
+            ..Self::new(member_count)
+        }
+    }
+
+    /// Make sure members can approve, revoke or reject the proposal.
+    pub fn check_open_for_votes(&self) -> Result<(), Error> {
+        match self.status {
+            ProposalStatus::Draft => Err(Error::ProposalIsDraft),
+            ProposalStatus::Executed => Err(Error::ProposalAlreadyExecuted),
+            ProposalStatus::Rejected | ProposalStatus::Cancelled | ProposalStatus::Expired => {
+                Err(Error::ProposalClosed)
+            }
+            ProposalStatus::Active | ProposalStatus::Approved => Ok(()),
         }
     }
 
     pub fn add_approval(&mut self, idx: usize, weight: u32) -> Result<(), Error> {
-        if self.members & (1 << idx as u64) != 0 {
+        if self.participates(idx) {
             return Err(Error::AlreadyParticipate);
         }
-        self.members |= 1 << idx as u64;
+        self.members.insert(idx)?;
         self.current_weight = self.current_weight.saturating_add(weight);
         Ok(())
     }
 
+    pub fn remove_approval(&mut self, idx: usize, weight: u32) -> Result<(), Error> {
+        if !self.members.contains(idx) {
+            return Err(Error::NotParticipating);
+        }
+        self.members.remove(idx);
+        self.current_weight = self.current_weight.saturating_sub(weight);
+        Ok(())
+    }
+
+    pub fn add_rejection(&mut self, idx: usize, weight: u32) -> Result<(), Error> {
+        if self.participates(idx) {
+            return Err(Error::AlreadyParticipate);
+        }
+        self.rejected_members.insert(idx)?;
+        self.rejected_weight = self.rejected_weight.saturating_add(weight);
+        Ok(())
+    }
+
+    /// Whether member has either approved or rejected the proposal.
+    fn participates(&self, idx: usize) -> bool {
+        self.members.contains(idx) || self.rejected_members.contains(idx)
+    }
+
     #[inline]
     pub fn current_weight(&self) -> u32 {
         self.current_weight
     }
 
-    #[cfg(test)]
+    #[inline]
+    pub fn rejected_weight(&self) -> u32 {
+        self.rejected_weight
+    }
+
+    pub fn mark_approved(&mut self, timestamp: i64) {
+        self.status = ProposalStatus::Approved;
+        self.approved_at = timestamp;
+    }
+
+    /// Return proposal back to collecting approvals, e.g. after an approval is revoked
+    /// or a draft is finalized.
+    pub fn mark_active(&mut self) {
+        self.status = ProposalStatus::Active;
+        self.approved_at = 0;
+    }
+
+    pub fn mark_executed(&mut self) {
+        self.status = ProposalStatus::Executed;
+    }
+
+    #[inline]
+    pub fn status(&self) -> ProposalStatus {
+        self.status
+    }
+
+    #[inline]
+    pub fn approved_at(&self) -> Option<i64> {
+        match self.status {
+            ProposalStatus::Approved | ProposalStatus::Executed => Some(self.approved_at),
+            ProposalStatus::Active
+            | ProposalStatus::Draft
+            | ProposalStatus::Rejected
+            | ProposalStatus::Cancelled
+            | ProposalStatus::Expired => None,
+        }
+    }
+
     pub fn is_approved_by(&self, idx: usize) -> bool {
-        self.members & (1 << idx as u64) != 0
+        self.members.contains(idx)
+    }
+
+    pub fn is_rejected_by(&self, idx: usize) -> bool {
+        self.rejected_members.contains(idx)
     }
 }
 
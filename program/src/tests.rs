@@ -1,34 +1,36 @@
-use crate::instruction::ApproveInstruction;
-use crate::instruction::MultiSigInstruction;
-use crate::instruction::ProposeInstruction;
-use crate::instruction::{InitInstruction, ProtectedAccountConfig};
+use crate::client::{self, group_address, proposal_address, protected_address, vault_address};
+use crate::ed25519;
+use crate::error::Error;
+use crate::event::Event;
+use crate::instruction::ProtectedAccountConfig;
 use crate::processor::pda_tag;
 use crate::state::{
-    AccountType, GroupData, GroupMember, ProposalConfig, ProposalData, ProposedAccountMeta,
-    ProposedInstruction,
+    unpack_account_data, AccountType, ApprovalMessage, GroupAccount, GroupData, GroupMember,
+    GroupVault, MemberSet, ProgramPolicy, ProposalConfig, ProposalData, ProposalExpiry,
+    ProposalHeader, ProposalReceipt, ProposalSeed, ProposalState, ProposalStatus,
+    ProposedInstruction, SpendingLimit, SpendingLimitConfig, ThresholdRule, Versioned,
 };
 
 use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::program_error::ProgramError;
+use solana_program::rent::Rent;
 use solana_program::system_instruction;
 use solana_program::{
     hash::hash,
-    instruction::{AccountMeta, Instruction},
+    instruction::{Instruction, InstructionError},
     pubkey::Pubkey,
 };
 use solana_program_test::ProgramTest;
 use solana_sdk::system_program::ID as SYSTEM_PROGRAM_ID;
 use solana_sdk::{
-    account::Account, signature::Keypair, signature::Signer, transaction::Transaction,
+    account::Account,
+    signature::Keypair,
+    signature::Signer,
+    transaction::{Transaction, TransactionError},
 };
 
-fn do_init(
-    program_id: Pubkey,
-    threshold: u32,
-    members: impl IntoIterator<Item = (Pubkey, u32)>,
-    payer: Pubkey,
-    protected_account_config: Option<ProtectedAccountConfig>,
-) -> (Transaction, Pubkey) {
-    let group_data = GroupData {
+fn group_data(threshold: u32, members: impl IntoIterator<Item = (Pubkey, u32)>) -> GroupData {
+    GroupData {
         members: members
             .into_iter()
             .map(|(key, weight)| GroupMember {
@@ -37,42 +39,43 @@ fn do_init(
             })
             .collect(),
         threshold,
-    };
+        execution_delay: 0,
+        threshold_rules: Vec::new(),
+        program_policy: ProgramPolicy::Any,
+    }
+}
 
-    let serialized = group_data.try_to_vec().unwrap();
-    let group_hash = hash(&serialized);
-    let (group_account, _) =
-        Pubkey::find_program_address(&[pda_tag::GROUP, group_hash.as_ref()], &program_id);
+fn do_init(
+    program_id: Pubkey,
+    threshold: u32,
+    members: impl IntoIterator<Item = (Pubkey, u32)>,
+    payer: Pubkey,
+    protected_account_config: Option<ProtectedAccountConfig>,
+) -> (Transaction, Pubkey) {
+    do_init_group(
+        program_id,
+        group_data(threshold, members),
+        payer,
+        protected_account_config,
+    )
+}
 
-    let do_init_protected = protected_account_config.is_some();
-    let command = MultiSigInstruction::Init(InitInstruction {
-        lamports: 1000,
+fn do_init_group(
+    program_id: Pubkey,
+    group_data: GroupData,
+    payer: Pubkey,
+    protected_account_config: Option<ProtectedAccountConfig>,
+) -> (Transaction, Pubkey) {
+    let group_account = group_address(&program_id, &group_data);
+    let instruction = client::init(
+        &program_id,
+        &payer,
         group_data,
+        1000,
+        0,
         protected_account_config,
-    });
-
-    let mut account_metas = vec![
-        AccountMeta::new_readonly(payer, true),
-        AccountMeta::new(group_account, false),
-        AccountMeta::new(SYSTEM_PROGRAM_ID, false),
-    ];
-    if do_init_protected {
-        let (protected_addr, _) = Pubkey::find_program_address(
-            &[pda_tag::PROTECTED, group_account.as_ref()],
-            &program_id,
-        );
-
-        account_metas.push(AccountMeta::new(protected_addr, false));
-    }
-
-    let transaction = Transaction::new_with_payer(
-        &[Instruction::new_with_borsh(
-            program_id,
-            &command,
-            account_metas,
-        )],
-        Some(&payer),
     );
+    let transaction = Transaction::new_with_payer(&[instruction], Some(&payer));
     (transaction, group_account)
 }
 
@@ -113,7 +116,9 @@ async fn init_works() {
         .unwrap()
         .data;
     assert_eq!(data[0], u8::from(AccountType::Group));
-    let group_data = GroupData::try_from_slice(&data[1..]).unwrap();
+    let group_data = unpack_account_data::<GroupAccount>(AccountType::Group, &data)
+        .unwrap()
+        .data;
     assert_eq!(group_data.threshold, threshold);
     assert!(group_data
         .members
@@ -121,8 +126,7 @@ async fn init_works() {
         .zip(users.into_iter())
         .all(|(member, (key, weight))| { member.public_key == key && member.weight == weight }));
 
-    let (protected_addr, _) =
-        Pubkey::find_program_address(&[pda_tag::PROTECTED, group_account.as_ref()], &program_id);
+    let protected_addr = protected_address(&program_id, &group_account);
     let protected_account = banks_client
         .get_account(protected_addr)
         .await
@@ -138,61 +142,55 @@ fn do_propose(
     group_account: Pubkey,
     proposed_instruction: Instruction,
 ) -> (Transaction, Pubkey) {
-    let (protected_account, _) = Pubkey::find_program_address(
-        &[pda_tag::PROTECTED, &group_account.to_bytes()[..]],
-        &program_id,
-    );
+    do_propose_with_expiry(
+        program_id,
+        signer,
+        payer,
+        group_account,
+        proposed_instruction,
+        None,
+    )
+}
 
-    let proposed_instruction = ProposedInstruction {
-        accounts: proposed_instruction
-            .accounts
-            .into_iter()
-            .map(|meta| ProposedAccountMeta {
-                pubkey: meta.pubkey,
-                is_signer: meta.is_signer,
-                is_writable: meta.is_writable,
-            })
-            .collect(),
-        program_id: proposed_instruction.program_id,
-        data: proposed_instruction.data,
-    };
-    let command = MultiSigInstruction::Propose(ProposeInstruction {
-        instructions: vec![proposed_instruction.clone()],
-        lamports: 100,
-        salt: 1
-    });
-    let proposal_config = ProposalConfig {
-        group: group_account,
-        instructions: vec![proposed_instruction.clone()],
-        author: signer,
-        salt: 1
-    };
-    let serialized = proposal_config.try_to_vec().unwrap();
-    let hash = hash(&serialized);
-    let (proposal_key, _) =
-        Pubkey::find_program_address(&[pda_tag::PROPOSAL, hash.as_ref()], &program_id);
-    let mut accounts = vec![
-        AccountMeta::new(signer, true),
-        AccountMeta::new(group_account, false),
-        AccountMeta::new(proposal_key, false),
-        AccountMeta::new_readonly(proposed_instruction.program_id, false),
-    ];
-    accounts.extend(proposed_instruction.accounts.iter().map(|acc| {
-        let is_signer = if acc.pubkey == protected_account {
-            false
-        } else {
-            acc.is_signer
-        };
-        if acc.is_writable {
-            AccountMeta::new(acc.pubkey, is_signer)
-        } else {
-            AccountMeta::new_readonly(acc.pubkey, is_signer)
-        }
-    }));
-    let transaction = Transaction::new_with_payer(
-        &[Instruction::new_with_borsh(program_id, &command, accounts)],
-        Some(&payer),
+fn do_propose_with_expiry(
+    program_id: Pubkey,
+    signer: Pubkey,
+    payer: Pubkey,
+    group_account: Pubkey,
+    proposed_instruction: Instruction,
+    expiry: Option<ProposalExpiry>,
+) -> (Transaction, Pubkey) {
+    do_propose_signed_by(
+        program_id,
+        signer,
+        payer,
+        group_account,
+        client::proposed_instruction(proposed_instruction, vec![0]),
+        expiry,
+    )
+}
+
+fn do_propose_signed_by(
+    program_id: Pubkey,
+    signer: Pubkey,
+    payer: Pubkey,
+    group_account: Pubkey,
+    proposed_instruction: ProposedInstruction,
+    expiry: Option<ProposalExpiry>,
+) -> (Transaction, Pubkey) {
+    // Tests store at most one proposal per group.
+    let proposal_key = proposal_address(&program_id, &group_account, 0);
+    let instruction = client::propose(
+        &program_id,
+        &signer,
+        &group_account,
+        0,
+        vec![proposed_instruction],
+        100,
+        1,
+        expiry,
     );
+    let transaction = Transaction::new_with_payer(&[instruction], Some(&payer));
     (transaction, proposal_key)
 }
 
@@ -228,10 +226,7 @@ async fn propose_over_threshold() {
     transaction.sign(&[&payer], recent_blockhash);
     banks_client.process_transaction(transaction).await.unwrap();
 
-    let (protected_account, _) = Pubkey::find_program_address(
-        &[pda_tag::PROTECTED, &group_account.to_bytes()[..]],
-        &program_id,
-    );
+    let protected_account = protected_address(&program_id, &group_account);
 
     // now propose
     let proposed_instruction = system_instruction::create_account(
@@ -257,12 +252,30 @@ async fn propose_over_threshold() {
         .is_none());
     transaction.sign(&[&payer, &alice], recent_blockhash);
     banks_client.process_transaction(transaction).await.unwrap();
-    // doesnt create proposal, executes immediately
-    assert!(banks_client
+    // proposal is approved right away, but not executed
+    let proposal = banks_client
         .get_account(proposal_acc)
         .await
         .unwrap()
+        .unwrap();
+    let proposal_data =
+        unpack_account_data::<ProposalData>(AccountType::Proposal, &proposal.data).unwrap();
+    assert_eq!(proposal_data.state.status(), ProposalStatus::Approved);
+    assert!(banks_client
+        .get_account(protected_account)
+        .await
+        .unwrap()
         .is_none());
+
+    let mut transaction = do_execute(
+        program_id,
+        alice_key,
+        payer.pubkey(),
+        proposal_data,
+        proposal_acc,
+    );
+    transaction.sign(&[&payer, &alice], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
     assert!(banks_client
         .get_account(protected_account)
         .await
@@ -270,6 +283,189 @@ async fn propose_over_threshold() {
         .is_some());
 }
 
+#[tokio::test]
+async fn update_group_keeps_addresses() {
+    let program_id = Pubkey::new_unique();
+    let alice = Keypair::new();
+    let alice_key = alice.pubkey();
+    let bob_key = Pubkey::new_unique();
+    let chris = Keypair::new();
+    let chris_key = chris.pubkey();
+    let dave_key = Pubkey::new_unique();
+
+    let mut program_test = ProgramTest::new(env!("CARGO_PKG_NAME"), program_id, None);
+    program_test.prefer_bpf(true);
+    program_test.add_program(env!("CARGO_PKG_NAME"), program_id, None);
+    for key in [alice_key, chris_key] {
+        program_test.add_account(
+            key,
+            Account {
+                lamports: 1000,
+                owner: SYSTEM_PROGRAM_ID,
+                ..Account::default()
+            },
+        );
+    }
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    // init first
+    let (mut transaction, group_account) = do_init(
+        program_id,
+        2,
+        vec![(alice_key, 2), (bob_key, 1), (chris_key, 1)],
+        payer.pubkey(),
+        None,
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    // chris alone is under threshold, so the proposal stays open
+    let (mut transaction, stale_proposal) = do_propose(
+        program_id,
+        chris_key,
+        payer.pubkey(),
+        group_account,
+        system_instruction::transfer(&chris_key, &dave_key, 1),
+    );
+    transaction.sign(&[&payer, &chris], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    // alice alone is over threshold, but the proposal isn't executed before the update
+    let approved_proposal = proposal_address(&program_id, &group_account, 1);
+    let instruction = client::propose(
+        &program_id,
+        &alice_key,
+        &group_account,
+        1,
+        vec![client::proposed_instruction(
+            system_instruction::transfer(
+                &protected_address(&program_id, &group_account),
+                &dave_key,
+                1,
+            ),
+            vec![0],
+        )],
+        100,
+        1,
+        None,
+    );
+    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &alice], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+    let approved_config = unpack_account_data::<ProposalData>(
+        AccountType::Proposal,
+        &banks_client
+            .get_account(approved_proposal)
+            .await
+            .unwrap()
+            .unwrap()
+            .data,
+    )
+    .unwrap()
+    .config;
+
+    // replace bob with dave and raise threshold
+    let new_users = vec![(alice_key, 2), (dave_key, 1), (chris_key, 1)];
+    let proposed_instruction = client::update_group(
+        &program_id,
+        &group_account,
+        group_data(3, new_users.clone()),
+    );
+
+    // alice alone is over threshold
+    let proposal_acc = proposal_address(&program_id, &group_account, 2);
+    let instruction = client::propose(
+        &program_id,
+        &alice_key,
+        &group_account,
+        2,
+        vec![client::proposed_instruction(proposed_instruction, vec![0])],
+        100,
+        1,
+        None,
+    );
+    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &alice], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let proposal = banks_client
+        .get_account(proposal_acc)
+        .await
+        .unwrap()
+        .unwrap();
+    let proposal_data =
+        unpack_account_data::<ProposalData>(AccountType::Proposal, &proposal.data).unwrap();
+    let mut transaction = do_execute(
+        program_id,
+        alice_key,
+        payer.pubkey(),
+        proposal_data,
+        proposal_acc,
+    );
+    transaction.sign(&[&payer, &alice], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let data = banks_client
+        .get_account(group_account)
+        .await
+        .unwrap()
+        .unwrap()
+        .data;
+    let group_data = unpack_account_data::<GroupAccount>(AccountType::Group, &data)
+        .unwrap()
+        .data;
+    assert_eq!(group_data.threshold, 3);
+    assert!(group_data
+        .members
+        .into_iter()
+        .zip(new_users.into_iter())
+        .all(|(member, (key, weight))| { member.public_key == key && member.weight == weight }));
+
+    // votes of the open proposals refer to the old members
+    let stale_instructions = [
+        (
+            client::approve(&program_id, &alice_key, &group_account, &stale_proposal),
+            &alice,
+        ),
+        (
+            client::reject(
+                &program_id,
+                &alice_key,
+                &group_account,
+                &stale_proposal,
+                &chris_key,
+                false,
+            ),
+            &alice,
+        ),
+        (
+            client::revoke_approval(&program_id, &chris_key, &group_account, &stale_proposal),
+            &chris,
+        ),
+        (
+            client::execute(
+                &program_id,
+                &alice_key,
+                &approved_proposal,
+                &approved_config,
+            ),
+            &alice,
+        ),
+    ];
+    for (instruction, signer) in stale_instructions {
+        let mut transaction = Transaction::new_with_payer(&[instruction], Some(&payer.pubkey()));
+        transaction.sign(&[&payer, signer], recent_blockhash);
+        assert_eq!(
+            banks_client
+                .process_transaction(transaction)
+                .await
+                .unwrap_err()
+                .unwrap(),
+            TransactionError::InstructionError(0, InstructionError::Custom(36))
+        );
+    }
+}
+
 #[tokio::test]
 async fn propose_not_over_threshold() {
     let program_id = Pubkey::new_unique();
@@ -299,10 +495,7 @@ async fn propose_not_over_threshold() {
     transaction.sign(&[&payer], recent_blockhash);
     banks_client.process_transaction(transaction).await.unwrap();
 
-    let (protected_account, _) = Pubkey::find_program_address(
-        &[pda_tag::PROTECTED, &group_account.to_bytes()[..]],
-        &program_id,
-    );
+    let protected_account = protected_address(&program_id, &group_account);
 
     // now propose
     let proposed_instruction = system_instruction::transfer(&protected_account, &destination, 100);
@@ -328,7 +521,8 @@ async fn propose_not_over_threshold() {
         .unwrap()
         .unwrap();
     assert_eq!(proposal.data[0], u8::from(AccountType::Proposal));
-    let proposal_data = ProposalData::try_from_slice(&proposal.data[1..]).unwrap();
+    let proposal_data =
+        unpack_account_data::<ProposalData>(AccountType::Proposal, &proposal.data).unwrap();
     assert!(proposal_data
         .state
         .is_approved_by(users.iter().position(|(key, _)| *key == bob_key).unwrap()));
@@ -360,38 +554,87 @@ fn do_approve(
     payer: Pubkey,
     proposal_data: ProposalData,
     proposal_acc: Pubkey,
-    protected_account: Pubkey,
 ) -> Transaction {
-    let group_acc = proposal_data.config.group;
-    let command = crate::instruction::MultiSigInstruction::Approve(ApproveInstruction {});
-
-    let mut accounts = vec![
-        AccountMeta::new(signer, true),
-        AccountMeta::new(group_acc, false),
-        AccountMeta::new(proposal_acc, false),
-        AccountMeta::new_readonly(proposal_data.config.instructions[0].program_id, false),
-    ];
-    accounts.extend(
-        proposal_data.config.instructions[0]
-            .accounts
-            .iter()
-            .map(|acc| {
-                let is_signer = if acc.pubkey == protected_account {
-                    false
-                } else {
-                    acc.is_signer
-                };
-                if acc.is_writable {
-                    AccountMeta::new(acc.pubkey, is_signer)
-                } else {
-                    AccountMeta::new_readonly(acc.pubkey, is_signer)
-                }
-            }),
-    );
-    Transaction::new_with_payer(
-        &[Instruction::new_with_borsh(program_id, &command, accounts)],
-        Some(&payer),
-    )
+    let instruction = client::approve(
+        &program_id,
+        &signer,
+        &proposal_data.config.group,
+        &proposal_acc,
+    );
+    Transaction::new_with_payer(&[instruction], Some(&payer))
+}
+
+fn do_execute(
+    program_id: Pubkey,
+    signer: Pubkey,
+    payer: Pubkey,
+    proposal_data: ProposalData,
+    proposal_acc: Pubkey,
+) -> Transaction {
+    let instruction = client::execute(&program_id, &signer, &proposal_acc, &proposal_data.config);
+    Transaction::new_with_payer(&[instruction], Some(&payer))
+}
+
+/// Execute an approved proposal, with the payer as the executor.
+async fn do_execute_proposal(
+    banks_client: &mut solana_program_test::BanksClient,
+    payer: &Keypair,
+    program_id: Pubkey,
+    proposal_acc: Pubkey,
+) {
+    let proposal = banks_client
+        .get_account(proposal_acc)
+        .await
+        .unwrap()
+        .unwrap();
+    let proposal_data =
+        unpack_account_data::<ProposalData>(AccountType::Proposal, &proposal.data).unwrap();
+    let mut transaction = do_execute(
+        program_id,
+        payer.pubkey(),
+        payer.pubkey(),
+        proposal_data,
+        proposal_acc,
+    );
+    let recent_blockhash = banks_client.get_recent_blockhash().await.unwrap();
+    transaction.sign(&[payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+}
+
+fn do_revoke_approval(
+    program_id: Pubkey,
+    signer: Pubkey,
+    payer: Pubkey,
+    proposal_data: ProposalData,
+    proposal_acc: Pubkey,
+) -> Transaction {
+    let instruction = client::revoke_approval(
+        &program_id,
+        &signer,
+        &proposal_data.config.group,
+        &proposal_acc,
+    );
+    Transaction::new_with_payer(&[instruction], Some(&payer))
+}
+
+fn do_reject(
+    program_id: Pubkey,
+    signer: Pubkey,
+    payer: Pubkey,
+    proposal_acc: Pubkey,
+    group_account: Pubkey,
+    author: Pubkey,
+    receipt: bool,
+) -> Transaction {
+    let instruction = client::reject(
+        &program_id,
+        &signer,
+        &group_account,
+        &proposal_acc,
+        &author,
+        receipt,
+    );
+    Transaction::new_with_payer(&[instruction], Some(&payer))
 }
 
 #[tokio::test]
@@ -438,10 +681,7 @@ async fn approve_over_threshold() {
     transaction.sign(&[&payer], recent_blockhash);
     banks_client.process_transaction(transaction).await.unwrap();
 
-    let (protected_account, _) = Pubkey::find_program_address(
-        &[pda_tag::PROTECTED, &group_account.to_bytes()[..]],
-        &program_id,
-    );
+    let protected_account = protected_address(&program_id, &group_account);
 
     // now propose
     let proposed_instruction =
@@ -488,31 +728,162 @@ async fn approve_over_threshold() {
         .unwrap()
         .unwrap();
     assert_eq!(proposal.data[0], u8::from(AccountType::Proposal));
-    let proposal_data = ProposalData::try_from_slice(&proposal.data[1..]).unwrap();
+    let proposal_data =
+        unpack_account_data::<ProposalData>(AccountType::Proposal, &proposal.data).unwrap();
+    // can't execute before the threshold is reached
+    let mut transaction = do_execute(
+        program_id,
+        chris.pubkey(),
+        payer.pubkey(),
+        unpack_account_data::<ProposalData>(AccountType::Proposal, &proposal.data).unwrap(),
+        proposal_acc,
+    );
+    transaction.sign(&[&chris, &payer], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_err());
+
     let mut transaction = do_approve(
         program_id,
         chris.pubkey(),
         payer.pubkey(),
-        proposal_data,
+        unpack_account_data::<ProposalData>(AccountType::Proposal, &proposal.data).unwrap(),
         proposal_acc,
-        protected_account,
     );
     transaction.sign(&[&chris, &payer], recent_blockhash);
     banks_client.process_transaction(transaction).await.unwrap();
 
-    let destination = banks_client
+    // approval only records the vote
+    assert!(banks_client
         .get_account(destination_acc)
         .await
         .unwrap()
-        .unwrap();
-    assert_eq!(destination.lamports, PROPOSED_LAMPORTS);
-    let proposal = banks_client
-        .get_account(proposal_acc)
-        .await
-        .unwrap()
-        .unwrap();
-    assert!(proposal.data.iter().all(|b| *b == 0));
-}
+        .is_none());
+
+    // anyone can execute
+    let mut transaction = do_execute(
+        program_id,
+        payer.pubkey(),
+        payer.pubkey(),
+        proposal_data,
+        proposal_acc,
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let destination = banks_client
+        .get_account(destination_acc)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(destination.lamports, PROPOSED_LAMPORTS);
+    let proposal = banks_client
+        .get_account(proposal_acc)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(proposal.data.iter().all(|b| *b == 0));
+}
+
+#[tokio::test]
+async fn execute_with_receipt() {
+    let program_id = Pubkey::new_unique();
+    let alice = Keypair::new();
+    let alice_key = alice.pubkey();
+    let bob = Keypair::new();
+    let bob_key = bob.pubkey();
+    let destination_acc = Pubkey::new_unique();
+
+    let mut program_test = ProgramTest::new(env!("CARGO_PKG_NAME"), program_id, None);
+    program_test.prefer_bpf(true);
+    program_test.add_program(env!("CARGO_PKG_NAME"), program_id, None);
+    for key in [alice_key, bob_key] {
+        program_test.add_account(
+            key,
+            Account {
+                lamports: 1000,
+                owner: SYSTEM_PROGRAM_ID,
+                ..Account::default()
+            },
+        );
+    }
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let (mut transaction, group_account) = do_init(
+        program_id,
+        2,
+        vec![(alice_key, 1), (bob_key, 1)],
+        payer.pubkey(),
+        Some(ProtectedAccountConfig {
+            space: 0,
+            owner: SYSTEM_PROGRAM_ID,
+            lamports: 1000,
+        }),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let protected_account = protected_address(&program_id, &group_account);
+    let (mut transaction, proposal_acc) = do_propose(
+        program_id,
+        alice_key,
+        payer.pubkey(),
+        group_account,
+        system_instruction::transfer(&protected_account, &destination_acc, 100),
+    );
+    transaction.sign(&[&payer, &alice], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let proposal = banks_client
+        .get_account(proposal_acc)
+        .await
+        .unwrap()
+        .unwrap();
+    let proposal_data =
+        unpack_account_data::<ProposalData>(AccountType::Proposal, &proposal.data).unwrap();
+    let config_hash = proposal_data.config.seed().unwrap();
+    let mut transaction = do_approve(
+        program_id,
+        bob_key,
+        payer.pubkey(),
+        unpack_account_data::<ProposalData>(AccountType::Proposal, &proposal.data).unwrap(),
+        proposal_acc,
+    );
+    transaction.sign(&[&payer, &bob], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let lamports = banks_client
+        .get_rent()
+        .await
+        .unwrap()
+        .minimum_balance(client::receipt_account_space() as usize);
+    let instruction = client::execute_with_receipt(
+        &program_id,
+        &payer.pubkey(),
+        &proposal_acc,
+        &proposal_data.config,
+        lamports,
+    );
+    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&payer.pubkey()));
+    transaction.sign(&[&payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    // the proposal account is closed, the receipt remains
+    assert!(banks_client
+        .get_account(proposal_acc)
+        .await
+        .unwrap()
+        .is_none());
+    let receipt = banks_client
+        .get_account(client::receipt_address(&program_id, &proposal_acc))
+        .await
+        .unwrap()
+        .unwrap();
+    let receipt =
+        unpack_account_data::<ProposalReceipt>(AccountType::Receipt, &receipt.data).unwrap();
+    assert_eq!(receipt.group, group_account);
+    assert_eq!(receipt.proposal, proposal_acc);
+    assert_eq!(receipt.config_hash, config_hash);
+    assert_eq!(receipt.status, ProposalStatus::Executed);
+}
 
 #[tokio::test]
 async fn approve_overflow() {
@@ -558,10 +929,7 @@ async fn approve_overflow() {
     transaction.sign(&[&payer], recent_blockhash);
     banks_client.process_transaction(transaction).await.unwrap();
 
-    let (protected_account, _) = Pubkey::find_program_address(
-        &[pda_tag::PROTECTED, &group_account.to_bytes()[..]],
-        &program_id,
-    );
+    let protected_account = protected_address(&program_id, &group_account);
 
     // now propose
     let proposed_instruction =
@@ -608,18 +976,28 @@ async fn approve_overflow() {
         .unwrap()
         .unwrap();
     assert_eq!(proposal.data[0], u8::from(AccountType::Proposal));
-    let proposal_data = ProposalData::try_from_slice(&proposal.data[1..]).unwrap();
+    let proposal_data =
+        unpack_account_data::<ProposalData>(AccountType::Proposal, &proposal.data).unwrap();
     let mut transaction = do_approve(
         program_id,
         chris.pubkey(),
         payer.pubkey(),
-        proposal_data,
+        unpack_account_data::<ProposalData>(AccountType::Proposal, &proposal.data).unwrap(),
         proposal_acc,
-        protected_account,
     );
     transaction.sign(&[&chris, &payer], recent_blockhash);
     banks_client.process_transaction(transaction).await.unwrap();
 
+    let mut transaction = do_execute(
+        program_id,
+        payer.pubkey(),
+        payer.pubkey(),
+        proposal_data,
+        proposal_acc,
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
     let destination = banks_client
         .get_account(destination_acc)
         .await
@@ -672,10 +1050,7 @@ async fn approve_twice_fails() {
     transaction.sign(&[&payer], recent_blockhash);
     banks_client.process_transaction(transaction).await.unwrap();
 
-    let (protected_account, _) = Pubkey::find_program_address(
-        &[pda_tag::PROTECTED, &group_account.to_bytes()[..]],
-        &program_id,
-    );
+    let protected_account = protected_address(&program_id, &group_account);
 
     // now propose
     let proposed_instruction =
@@ -722,15 +1097,2193 @@ async fn approve_twice_fails() {
         .unwrap()
         .unwrap();
     assert_eq!(proposal.data[0], u8::from(AccountType::Proposal));
-    let proposal_data = ProposalData::try_from_slice(&proposal.data[1..]).unwrap();
+    let proposal_data =
+        unpack_account_data::<ProposalData>(AccountType::Proposal, &proposal.data).unwrap();
     let mut transaction = do_approve(
         program_id,
         bob.pubkey(),
         payer.pubkey(),
         proposal_data,
         proposal_acc,
-        protected_account,
     );
     transaction.sign(&[&bob, &payer], recent_blockhash);
     assert!(banks_client.process_transaction(transaction).await.is_err());
 }
+
+fn do_close_proposal(
+    program_id: Pubkey,
+    signer: Pubkey,
+    payer: Pubkey,
+    proposal_acc: Pubkey,
+    destination: Pubkey,
+    receipt: bool,
+) -> Transaction {
+    let instruction =
+        client::close_proposal(&program_id, &signer, &proposal_acc, &destination, receipt);
+    Transaction::new_with_payer(&[instruction], Some(&payer))
+}
+
+#[tokio::test]
+async fn expired_proposal() {
+    const PROPOSED_LAMPORTS: u64 = 50;
+
+    let program_id = Pubkey::new_unique();
+    let alice_key = Pubkey::new_unique();
+    let bob = Keypair::new();
+    let bob_key = bob.pubkey();
+    let chris = Keypair::new();
+    let chris_key = chris.pubkey();
+    let destination_acc = Pubkey::new_unique();
+
+    let mut program_test = ProgramTest::new(env!("CARGO_PKG_NAME"), program_id, None);
+    program_test.prefer_bpf(true);
+    program_test.add_program(env!("CARGO_PKG_NAME"), program_id, None);
+    program_test.add_account(
+        bob_key,
+        Account {
+            lamports: 1000,
+            owner: SYSTEM_PROGRAM_ID,
+            ..Account::default()
+        },
+    );
+    program_test.add_account(
+        chris_key,
+        Account {
+            lamports: 1000,
+            owner: SYSTEM_PROGRAM_ID,
+            ..Account::default()
+        },
+    );
+
+    let mut context = program_test.start_with_context().await;
+    let payer = Keypair::from_bytes(&context.payer.to_bytes()).unwrap();
+    let recent_blockhash = context.last_blockhash;
+    // init first
+    let (mut transaction, group_account) = do_init(
+        program_id,
+        2,
+        vec![(alice_key, 2), (bob_key, 1), (chris_key, 1)],
+        payer.pubkey(),
+        None,
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let protected_account = protected_address(&program_id, &group_account);
+
+    // propose with expiry
+    let proposed_instruction =
+        system_instruction::transfer(&protected_account, &destination_acc, PROPOSED_LAMPORTS);
+    let expiry_slot = context.banks_client.get_root_slot().await.unwrap() + 10;
+    let (mut transaction, proposal_acc) = do_propose_with_expiry(
+        program_id,
+        bob_key,
+        payer.pubkey(),
+        group_account,
+        proposed_instruction,
+        Some(ProposalExpiry::Slot(expiry_slot)),
+    );
+    transaction.sign(&[&payer, &bob], recent_blockhash);
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    // closing by someone else fails before expiry
+    let mut transaction = do_close_proposal(
+        program_id,
+        payer.pubkey(),
+        payer.pubkey(),
+        proposal_acc,
+        bob_key,
+        false,
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    assert!(context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .is_err());
+
+    context.warp_to_slot(expiry_slot + 1).unwrap();
+    let recent_blockhash = context.banks_client.get_recent_blockhash().await.unwrap();
+
+    // approve fails after expiry
+    let proposal = context
+        .banks_client
+        .get_account(proposal_acc)
+        .await
+        .unwrap()
+        .unwrap();
+    let proposal_lamports = proposal.lamports;
+    let proposal_data =
+        unpack_account_data::<ProposalData>(AccountType::Proposal, &proposal.data).unwrap();
+    let mut transaction = do_approve(
+        program_id,
+        chris_key,
+        payer.pubkey(),
+        proposal_data,
+        proposal_acc,
+    );
+    transaction.sign(&[&chris, &payer], recent_blockhash);
+    assert!(context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .is_err());
+
+    // anyone can close, but lamports go to the author
+    let mut transaction = do_close_proposal(
+        program_id,
+        payer.pubkey(),
+        payer.pubkey(),
+        proposal_acc,
+        payer.pubkey(),
+        false,
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    assert!(context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .is_err());
+
+    let bob_lamports = context
+        .banks_client
+        .get_account(bob_key)
+        .await
+        .unwrap()
+        .unwrap()
+        .lamports;
+    let mut transaction = do_close_proposal(
+        program_id,
+        payer.pubkey(),
+        payer.pubkey(),
+        proposal_acc,
+        bob_key,
+        true,
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    // the receipt records the expiry, its rent is taken from the proposal lamports
+    let receipt_account = context
+        .banks_client
+        .get_account(client::receipt_address(&program_id, &proposal_acc))
+        .await
+        .unwrap()
+        .unwrap();
+    let receipt =
+        unpack_account_data::<ProposalReceipt>(AccountType::Receipt, &receipt_account.data)
+            .unwrap();
+    assert_eq!(receipt.status, ProposalStatus::Expired);
+    let bob_account = context
+        .banks_client
+        .get_account(bob_key)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        bob_account.lamports,
+        bob_lamports + proposal_lamports - receipt_account.lamports
+    );
+}
+
+#[tokio::test]
+async fn execute_after_delay() {
+    const PROPOSED_LAMPORTS: u64 = 50;
+    const EXECUTION_DELAY: u64 = 60;
+
+    let program_id = Pubkey::new_unique();
+    let alice_key = Pubkey::new_unique();
+    let bob = Keypair::new();
+    let bob_key = bob.pubkey();
+    let chris = Keypair::new();
+    let chris_key = chris.pubkey();
+    let destination_acc = Pubkey::new_unique();
+
+    let mut program_test = ProgramTest::new(env!("CARGO_PKG_NAME"), program_id, None);
+    program_test.prefer_bpf(true);
+    program_test.add_program(env!("CARGO_PKG_NAME"), program_id, None);
+    program_test.add_account(
+        bob_key,
+        Account {
+            lamports: 1000,
+            owner: SYSTEM_PROGRAM_ID,
+            ..Account::default()
+        },
+    );
+    program_test.add_account(
+        chris_key,
+        Account {
+            lamports: 1000,
+            owner: SYSTEM_PROGRAM_ID,
+            ..Account::default()
+        },
+    );
+
+    let mut context = program_test.start_with_context().await;
+    let payer = Keypair::from_bytes(&context.payer.to_bytes()).unwrap();
+    let recent_blockhash = context.last_blockhash;
+    // init first
+    let mut group = group_data(2, vec![(alice_key, 2), (bob_key, 1), (chris_key, 1)]);
+    group.execution_delay = EXECUTION_DELAY;
+    let (mut transaction, group_account) = do_init_group(program_id, group, payer.pubkey(), None);
+    transaction.sign(&[&payer], recent_blockhash);
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let protected_account = protected_address(&program_id, &group_account);
+    do_transfer(&mut context.banks_client, &payer, protected_account, 100).await;
+
+    // now propose and approve
+    let proposed_instruction =
+        system_instruction::transfer(&protected_account, &destination_acc, PROPOSED_LAMPORTS);
+    let (mut transaction, proposal_acc) = do_propose(
+        program_id,
+        bob_key,
+        payer.pubkey(),
+        group_account,
+        proposed_instruction,
+    );
+    transaction.sign(&[&payer, &bob], recent_blockhash);
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let proposal = context
+        .banks_client
+        .get_account(proposal_acc)
+        .await
+        .unwrap()
+        .unwrap();
+    let proposal_data =
+        unpack_account_data::<ProposalData>(AccountType::Proposal, &proposal.data).unwrap();
+    let mut transaction = do_approve(
+        program_id,
+        chris_key,
+        payer.pubkey(),
+        proposal_data,
+        proposal_acc,
+    );
+    transaction.sign(&[&chris, &payer], recent_blockhash);
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    // threshold is reached, but nothing is executed yet
+    assert!(context
+        .banks_client
+        .get_account(destination_acc)
+        .await
+        .unwrap()
+        .is_none());
+    let proposal = context
+        .banks_client
+        .get_account(proposal_acc)
+        .await
+        .unwrap()
+        .unwrap();
+    let proposal_data =
+        unpack_account_data::<ProposalData>(AccountType::Proposal, &proposal.data).unwrap();
+    assert!(proposal_data.state.approved_at().is_some());
+
+    let mut transaction = do_execute(
+        program_id,
+        chris_key,
+        payer.pubkey(),
+        unpack_account_data::<ProposalData>(AccountType::Proposal, &proposal.data).unwrap(),
+        proposal_acc,
+    );
+    transaction.sign(&[&chris, &payer], recent_blockhash);
+    assert!(context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .is_err());
+
+    // a slot is at least 400ms, so this is well past the delay
+    let slot = context.banks_client.get_root_slot().await.unwrap();
+    context.warp_to_slot(slot + 1000).unwrap();
+    let recent_blockhash = context.banks_client.get_recent_blockhash().await.unwrap();
+
+    let mut transaction = do_execute(
+        program_id,
+        chris_key,
+        payer.pubkey(),
+        proposal_data,
+        proposal_acc,
+    );
+    transaction.sign(&[&chris, &payer], recent_blockhash);
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let destination = context
+        .banks_client
+        .get_account(destination_acc)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(destination.lamports, PROPOSED_LAMPORTS);
+}
+
+#[tokio::test]
+async fn revoke_approval() {
+    const PROPOSED_LAMPORTS: u64 = 50;
+
+    let program_id = Pubkey::new_unique();
+    let alice_key = Pubkey::new_unique();
+    let bob = Keypair::new();
+    let bob_key = bob.pubkey();
+    let chris = Keypair::new();
+    let chris_key = chris.pubkey();
+    let destination_acc = Pubkey::new_unique();
+
+    let mut program_test = ProgramTest::new(env!("CARGO_PKG_NAME"), program_id, None);
+    program_test.prefer_bpf(true);
+    program_test.add_program(env!("CARGO_PKG_NAME"), program_id, None);
+    program_test.add_account(
+        bob_key,
+        Account {
+            lamports: 1000,
+            owner: SYSTEM_PROGRAM_ID,
+            ..Account::default()
+        },
+    );
+    program_test.add_account(
+        chris_key,
+        Account {
+            lamports: 1000,
+            owner: SYSTEM_PROGRAM_ID,
+            ..Account::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let users = vec![(alice_key, 2), (bob_key, 1), (chris_key, 1)];
+    // init first
+    let (mut transaction, group_account) =
+        do_init(program_id, 2, users.clone(), payer.pubkey(), None);
+    transaction.sign(&[&payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let protected_account = protected_address(&program_id, &group_account);
+    do_transfer(&mut banks_client, &payer, protected_account, 100).await;
+
+    // now propose and approve
+    let proposed_instruction =
+        system_instruction::transfer(&protected_account, &destination_acc, PROPOSED_LAMPORTS);
+    let (mut transaction, proposal_acc) = do_propose(
+        program_id,
+        bob_key,
+        payer.pubkey(),
+        group_account,
+        proposed_instruction,
+    );
+    transaction.sign(&[&payer, &bob], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let proposal = banks_client
+        .get_account(proposal_acc)
+        .await
+        .unwrap()
+        .unwrap();
+    let proposal_data =
+        unpack_account_data::<ProposalData>(AccountType::Proposal, &proposal.data).unwrap();
+    let mut transaction = do_approve(
+        program_id,
+        chris_key,
+        payer.pubkey(),
+        proposal_data,
+        proposal_acc,
+    );
+    transaction.sign(&[&chris, &payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    // chris revokes the approval
+    let proposal = banks_client
+        .get_account(proposal_acc)
+        .await
+        .unwrap()
+        .unwrap();
+    let proposal_data =
+        unpack_account_data::<ProposalData>(AccountType::Proposal, &proposal.data).unwrap();
+    assert_eq!(proposal_data.state.status(), ProposalStatus::Approved);
+    let mut transaction = do_revoke_approval(
+        program_id,
+        chris_key,
+        payer.pubkey(),
+        proposal_data,
+        proposal_acc,
+    );
+    transaction.sign(&[&chris, &payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let proposal = banks_client
+        .get_account(proposal_acc)
+        .await
+        .unwrap()
+        .unwrap();
+    let proposal_data =
+        unpack_account_data::<ProposalData>(AccountType::Proposal, &proposal.data).unwrap();
+    let chris_index = users.iter().position(|(key, _)| *key == chris_key).unwrap();
+    assert!(!proposal_data.state.is_approved_by(chris_index));
+    assert_eq!(proposal_data.state.current_weight(), 1);
+    assert_eq!(proposal_data.state.status(), ProposalStatus::Active);
+
+    // proposal is below the threshold again
+    let mut transaction = do_execute(
+        program_id,
+        payer.pubkey(),
+        payer.pubkey(),
+        proposal_data,
+        proposal_acc,
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_err());
+}
+
+#[tokio::test]
+async fn revoked_signature_approval_cant_be_replayed() {
+    let program_id = Pubkey::new_unique();
+    let alice = Keypair::new();
+    let alice_key = alice.pubkey();
+    let bob_key = Pubkey::new_unique();
+    let chris = Keypair::new();
+    let chris_key = chris.pubkey();
+
+    let mut program_test = ProgramTest::new(env!("CARGO_PKG_NAME"), program_id, None);
+    program_test.prefer_bpf(true);
+    program_test.add_program(env!("CARGO_PKG_NAME"), program_id, None);
+    program_test.add_account(
+        chris_key,
+        Account {
+            lamports: 1_000_000_000,
+            owner: SYSTEM_PROGRAM_ID,
+            ..Account::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let users = vec![(alice_key, 1), (bob_key, 1), (chris_key, 1)];
+    let (mut transaction, group_account) =
+        do_init(program_id, 3, users.clone(), payer.pubkey(), None);
+    transaction.sign(&[&payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let proposed_instruction = system_instruction::transfer(
+        &protected_address(&program_id, &group_account),
+        &Pubkey::new_unique(),
+        50,
+    );
+    let (mut transaction, proposal_acc) = do_propose(
+        program_id,
+        alice_key,
+        payer.pubkey(),
+        group_account,
+        proposed_instruction,
+    );
+    transaction.sign(&[&payer, &alice], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    // chris approves with a signature, then revokes the approval
+    let proposal = banks_client
+        .get_account(proposal_acc)
+        .await
+        .unwrap()
+        .unwrap();
+    let proposal_data =
+        unpack_account_data::<ProposalData>(AccountType::Proposal, &proposal.data).unwrap();
+    let message = ApprovalMessage::new(
+        &program_id,
+        &proposal_acc,
+        &proposal_data.config,
+        proposal_data.approval_nonce,
+    )
+    .unwrap()
+    .to_bytes()
+    .unwrap();
+    let mut signature = [0; 64];
+    signature.copy_from_slice(chris.sign_message(&message).as_ref());
+    let approve_instructions = client::approve_with_signatures(
+        &program_id,
+        &proposal_acc,
+        &proposal_data.config,
+        proposal_data.approval_nonce,
+        &[(chris_key, signature)],
+    );
+    let mut transaction = Transaction::new_with_payer(&approve_instructions, Some(&payer.pubkey()));
+    transaction.sign(&[&payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let mut transaction = do_revoke_approval(
+        program_id,
+        chris_key,
+        payer.pubkey(),
+        proposal_data,
+        proposal_acc,
+    );
+    transaction.sign(&[&chris, &payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    // anyone replaying the signed approval is refused
+    let mut transaction = Transaction::new_with_payer(&approve_instructions, Some(&chris_key));
+    transaction.sign(&[&chris], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_err());
+
+    let proposal = banks_client
+        .get_account(proposal_acc)
+        .await
+        .unwrap()
+        .unwrap();
+    let proposal_data =
+        unpack_account_data::<ProposalData>(AccountType::Proposal, &proposal.data).unwrap();
+    let chris_index = users.iter().position(|(key, _)| *key == chris_key).unwrap();
+    assert!(!proposal_data.state.is_approved_by(chris_index));
+    assert_eq!(proposal_data.approval_nonce, 1);
+    assert_eq!(proposal_data.state.current_weight(), 1);
+}
+
+#[tokio::test]
+async fn reject_closes_unreachable_proposal() {
+    let program_id = Pubkey::new_unique();
+    let alice = Keypair::new();
+    let alice_key = alice.pubkey();
+    let bob = Keypair::new();
+    let bob_key = bob.pubkey();
+    let chris = Keypair::new();
+    let chris_key = chris.pubkey();
+    let destination_acc = Pubkey::new_unique();
+
+    let mut program_test = ProgramTest::new(env!("CARGO_PKG_NAME"), program_id, None);
+    program_test.prefer_bpf(true);
+    program_test.add_program(env!("CARGO_PKG_NAME"), program_id, None);
+    for key in &[alice_key, bob_key, chris_key] {
+        program_test.add_account(
+            *key,
+            Account {
+                lamports: 1000,
+                owner: SYSTEM_PROGRAM_ID,
+                ..Account::default()
+            },
+        );
+    }
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let users = vec![(alice_key, 2), (bob_key, 1), (chris_key, 1)];
+    // init first
+    let (mut transaction, group_account) =
+        do_init(program_id, 3, users.clone(), payer.pubkey(), None);
+    transaction.sign(&[&payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let protected_account = protected_address(&program_id, &group_account);
+
+    // now propose
+    let proposed_instruction =
+        system_instruction::transfer(&protected_account, &destination_acc, 50);
+    let (mut transaction, proposal_acc) = do_propose(
+        program_id,
+        bob_key,
+        payer.pubkey(),
+        group_account,
+        proposed_instruction,
+    );
+    transaction.sign(&[&payer, &bob], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+    let bob_lamports = banks_client
+        .get_account(bob_key)
+        .await
+        .unwrap()
+        .unwrap()
+        .lamports;
+
+    // chris rejects, alice and bob can still reach the threshold
+    let mut transaction = do_reject(
+        program_id,
+        chris_key,
+        payer.pubkey(),
+        proposal_acc,
+        group_account,
+        bob_key,
+        false,
+    );
+    transaction.sign(&[&chris, &payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let proposal = banks_client
+        .get_account(proposal_acc)
+        .await
+        .unwrap()
+        .unwrap();
+    let proposal_lamports = proposal.lamports;
+    let proposal_data =
+        unpack_account_data::<ProposalData>(AccountType::Proposal, &proposal.data).unwrap();
+    let config_hash = proposal_data.config.seed().unwrap();
+    let chris_index = users.iter().position(|(key, _)| *key == chris_key).unwrap();
+    assert!(proposal_data.state.is_rejected_by(chris_index));
+    assert_eq!(proposal_data.state.rejected_weight(), 1);
+
+    // chris can't approve after rejecting
+    let mut transaction = do_approve(
+        program_id,
+        chris_key,
+        payer.pubkey(),
+        proposal_data,
+        proposal_acc,
+    );
+    transaction.sign(&[&chris, &payer], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_err());
+
+    // alice rejects, threshold is unreachable
+    let mut transaction = do_reject(
+        program_id,
+        alice_key,
+        payer.pubkey(),
+        proposal_acc,
+        group_account,
+        bob_key,
+        true,
+    );
+    transaction.sign(&[&alice, &payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    assert!(banks_client
+        .get_account(proposal_acc)
+        .await
+        .unwrap()
+        .is_none());
+    // the receipt records the rejection, its rent is taken from the proposal lamports
+    let receipt_account = banks_client
+        .get_account(client::receipt_address(&program_id, &proposal_acc))
+        .await
+        .unwrap()
+        .unwrap();
+    let receipt =
+        unpack_account_data::<ProposalReceipt>(AccountType::Receipt, &receipt_account.data)
+            .unwrap();
+    assert_eq!(receipt.status, ProposalStatus::Rejected);
+    assert_eq!(receipt.proposal, proposal_acc);
+    assert_eq!(receipt.config_hash, config_hash);
+    let bob_account = banks_client.get_account(bob_key).await.unwrap().unwrap();
+    assert_eq!(
+        bob_account.lamports,
+        bob_lamports + proposal_lamports - receipt_account.lamports
+    );
+}
+
+#[test]
+fn member_set_beyond_64_members() {
+    let mut set = MemberSet::new(200);
+    set.insert(0).unwrap();
+    set.insert(64).unwrap();
+    set.insert(199).unwrap();
+    assert!(set.contains(0) && set.contains(64) && set.contains(199));
+    assert!(!set.contains(63) && !set.contains(65) && !set.contains(198));
+    set.remove(64);
+    assert!(!set.contains(64));
+    // members that joined after the set was created are out of range
+    assert!(set.insert(200).is_err());
+    assert!(!set.contains(200));
+
+    let mut state = ProposalState::new(200);
+    state.add_approval(150, 3).unwrap();
+    assert!(state.add_approval(150, 3).is_err());
+    assert!(state.add_rejection(150, 3).is_err());
+    state.add_rejection(151, 2).unwrap();
+    assert!(state.is_approved_by(150) && state.is_rejected_by(151));
+    assert_eq!(state.current_weight(), 3);
+    assert_eq!(state.rejected_weight(), 2);
+}
+
+#[test]
+fn proposal_header_is_fixed_prefix() {
+    let instruction = ProposedInstruction {
+        program_id: Pubkey::new_unique(),
+        accounts: Vec::new(),
+        data: vec![1; 100],
+        vaults: vec![0],
+    };
+    for expiry in [None, Some(ProposalExpiry::Slot(42))] {
+        let mut state = ProposalState::new(200);
+        state.add_approval(150, 3).unwrap();
+        let proposal = ProposalData {
+            seed: ProposalSeed::Index(u64::MAX),
+            bump: 255,
+            config: ProposalConfig {
+                group: Pubkey::new_unique(),
+                instructions: vec![instruction.clone(); 3],
+                author: Pubkey::new_unique(),
+                salt: 7,
+                expiry,
+            },
+            state,
+            threshold: 2,
+            group_version: 3,
+            approval_nonce: 4,
+        };
+        let header = proposal.header().try_to_vec().unwrap();
+        assert_eq!(header.len(), ProposalHeader::LEN);
+
+        let mut data = proposal.try_to_vec().unwrap();
+        assert_eq!(data[..ProposalHeader::LEN], header[..]);
+        assert_eq!(
+            ProposalHeader::try_from_slice(&data[..ProposalHeader::LEN]).unwrap(),
+            proposal.header()
+        );
+
+        // Votes rewrite the header in place, instructions stay as they are.
+        let mut header = proposal.header();
+        header.state.add_rejection(151, 2).unwrap();
+        data[..ProposalHeader::LEN].copy_from_slice(&header.try_to_vec().unwrap());
+        let updated = ProposalData::try_from_slice(&data).unwrap();
+        assert!(updated.state.is_rejected_by(151));
+        assert_eq!(updated.config.instructions.len(), 3);
+        assert_eq!(updated.config.expiry, expiry);
+    }
+}
+
+#[test]
+fn account_layout_versions() {
+    let group = GroupAccount {
+        seed: hash(b"seed"),
+        bump: 255,
+        vaults: vec![GroupVault {
+            index: 0,
+            bump: 254,
+        }],
+        data: group_data(1, vec![(Pubkey::new_unique(), 1)]),
+        proposal_count: 5,
+        config_version: 2,
+    };
+    let mut data = vec![u8::from(AccountType::Group), GroupAccount::VERSION];
+    data.extend(group.try_to_vec().unwrap());
+    let decoded = unpack_account_data::<GroupAccount>(AccountType::Group, &data).unwrap();
+    assert_eq!(decoded.vaults, group.vaults);
+    assert_eq!(decoded.proposal_count, group.proposal_count);
+    assert_eq!(decoded.config_version, group.config_version);
+
+    assert!(matches!(
+        unpack_account_data::<GroupAccount>(AccountType::Proposal, &data),
+        Err(Error::InvalidAccountType)
+    ));
+    // Accounts written by a newer program version can't be read.
+    for version in [0, GroupAccount::VERSION + 1] {
+        data[1] = version;
+        assert!(matches!(
+            unpack_account_data::<GroupAccount>(AccountType::Group, &data),
+            Err(Error::UnsupportedAccountVersion)
+        ));
+    }
+
+    // The first program release stored group data with no version, keyed by its hash.
+    let members: Vec<_> = (0..3).map(|_| Pubkey::new_unique()).collect();
+    let mut baseline_group = vec![1, 3, 0, 0, 0];
+    for (member, weight) in members.iter().zip([1u8, 2, 3]) {
+        baseline_group.extend(member.as_ref());
+        baseline_group.extend([weight, 0, 0, 0]);
+    }
+    baseline_group.extend([4, 0, 0, 0]);
+    let decoded = unpack_account_data::<GroupAccount>(AccountType::Group, &baseline_group).unwrap();
+    assert_eq!(decoded.seed, hash(&baseline_group[1..]));
+    assert_eq!(decoded.data.members.len(), 3);
+    assert_eq!(decoded.data.members[2].public_key, members[2]);
+    assert_eq!(decoded.data.members[2].weight, 3);
+    assert_eq!(decoded.data.threshold, 4);
+    assert_eq!(decoded.data.execution_delay, 0);
+    assert!(decoded.vaults.is_empty());
+    assert!(decoded.data.threshold_rules.is_empty());
+    assert_eq!(decoded.data.program_policy, ProgramPolicy::Any);
+    assert_eq!(decoded.proposal_count, 0);
+    assert_eq!(decoded.config_version, 0);
+    let baseline_group_data = decoded.data;
+
+    // Its proposals stored the config, keyed by its hash, followed by the approvals.
+    let group_key = Pubkey::new_unique();
+    let program = Pubkey::new_unique();
+    let account = Pubkey::new_unique();
+    let author = Pubkey::new_unique();
+    let mut baseline_proposal = vec![2];
+    baseline_proposal.extend(group_key.as_ref());
+    baseline_proposal.extend([1, 0, 0, 0]);
+    baseline_proposal.extend(program.as_ref());
+    baseline_proposal.extend([1, 0, 0, 0]);
+    baseline_proposal.extend(account.as_ref());
+    baseline_proposal.extend([0, 1]);
+    baseline_proposal.extend([2, 0, 0, 0, 7, 8]);
+    baseline_proposal.extend(author.as_ref());
+    baseline_proposal.extend(9u64.to_le_bytes());
+    let config_end = baseline_proposal.len();
+    baseline_proposal.extend(0b101u64.to_le_bytes());
+    baseline_proposal.extend(4u32.to_le_bytes());
+    let decoded =
+        unpack_account_data::<ProposalData>(AccountType::Proposal, &baseline_proposal).unwrap();
+    assert_eq!(
+        decoded.seed,
+        ProposalSeed::Config(hash(&baseline_proposal[1..config_end]))
+    );
+    assert_eq!(decoded.config.group, group_key);
+    assert_eq!(decoded.config.author, author);
+    assert_eq!(decoded.config.salt, 9);
+    assert_eq!(decoded.config.expiry, None);
+    let instruction = &decoded.config.instructions[0];
+    assert_eq!(instruction.program_id, program);
+    assert_eq!(instruction.accounts[0].pubkey, account);
+    assert!(!instruction.accounts[0].is_signer);
+    assert!(instruction.accounts[0].is_writable);
+    assert_eq!(instruction.data, vec![7, 8]);
+    assert_eq!(instruction.vaults, vec![0]);
+    assert_eq!(decoded.state.status(), ProposalStatus::Active);
+    assert!(decoded.state.is_approved_by(0));
+    assert!(!decoded.state.is_approved_by(1));
+    assert!(decoded.state.is_approved_by(2));
+    assert_eq!(decoded.state.current_weight(), 4);
+    assert_eq!(decoded.group_version, 0);
+    let header =
+        unpack_account_data::<ProposalHeader>(AccountType::Proposal, &baseline_proposal).unwrap();
+    assert_eq!(header, decoded.header());
+    assert_eq!(header.threshold(&baseline_group_data), 4);
+
+    assert!(matches!(
+        unpack_account_data::<GroupAccount>(AccountType::Group, &data[..1]),
+        Err(Error::EmptyAccountData)
+    ));
+}
+
+#[test]
+fn threshold_rules() {
+    let multisig_program = Pubkey::new_unique();
+    let token_program = Pubkey::new_unique();
+    let loader = Pubkey::new_unique();
+    let mut group = group_data(3, (0..5).map(|_| (Pubkey::new_unique(), 1)));
+    group.threshold_rules = vec![
+        ThresholdRule {
+            program_id: token_program,
+            data_prefix: vec![],
+            threshold: 2,
+        },
+        ThresholdRule {
+            program_id: loader,
+            data_prefix: vec![3, 0, 0, 0],
+            threshold: 4,
+        },
+    ];
+    group.validate().unwrap();
+    let instruction = |program_id, data: &[u8]| ProposedInstruction {
+        program_id,
+        accounts: vec![],
+        data: data.to_vec(),
+        vaults: vec![0],
+    };
+
+    assert_eq!(group.proposal_threshold(&[], &multisig_program), 3);
+    assert_eq!(
+        group.proposal_threshold(&[instruction(token_program, &[3])], &multisig_program),
+        2
+    );
+    // the prefix doesn't match, so the group threshold applies
+    assert_eq!(
+        group.proposal_threshold(&[instruction(loader, &[4, 0, 0, 0])], &multisig_program),
+        3
+    );
+    assert_eq!(
+        group.proposal_threshold(
+            &[
+                instruction(token_program, &[3]),
+                instruction(loader, &[3, 0, 0, 0, 1]),
+            ],
+            &multisig_program
+        ),
+        4
+    );
+
+    // Group updates need the highest threshold, even if a rule for the multisig program is lower.
+    group.threshold_rules.push(ThresholdRule {
+        program_id: multisig_program,
+        data_prefix: vec![],
+        threshold: 1,
+    });
+    assert_eq!(
+        group.proposal_threshold(&[instruction(multisig_program, &[])], &multisig_program),
+        4
+    );
+
+    group.threshold_rules[1].threshold = 6;
+    assert!(matches!(group.validate(), Err(Error::UnreachableThreshold)));
+}
+
+#[test]
+fn program_policy() {
+    let multisig = Pubkey::new_unique();
+    let token_program = Pubkey::new_unique();
+    let other = Pubkey::new_unique();
+    let instruction = |program_id| ProposedInstruction {
+        program_id,
+        accounts: vec![],
+        data: vec![],
+        vaults: vec![0],
+    };
+    let mut group = group_data(1, vec![(Pubkey::new_unique(), 1)]);
+    group
+        .check_programs(&[instruction(token_program), instruction(other)], &multisig)
+        .unwrap();
+
+    group.program_policy = ProgramPolicy::Allow(vec![token_program]);
+    group
+        .check_programs(&[instruction(token_program)], &multisig)
+        .unwrap();
+    assert!(matches!(
+        group.check_programs(&[instruction(token_program), instruction(other)], &multisig),
+        Err(Error::ProgramNotAllowed)
+    ));
+
+    group.program_policy = ProgramPolicy::Deny(vec![other, multisig]);
+    group
+        .check_programs(&[instruction(token_program)], &multisig)
+        .unwrap();
+    assert!(matches!(
+        group.check_programs(&[instruction(other)], &multisig),
+        Err(Error::ProgramNotAllowed)
+    ));
+    // the group can always update its policy
+    group
+        .check_programs(&[instruction(multisig)], &multisig)
+        .unwrap();
+}
+
+#[test]
+fn approval_message_format() {
+    let program_id = Pubkey::new_unique();
+    let proposal = Pubkey::new_unique();
+    let config = ProposalConfig {
+        group: Pubkey::new_unique(),
+        instructions: vec![],
+        author: Pubkey::new_unique(),
+        salt: 1,
+        expiry: Some(ProposalExpiry::Slot(42)),
+    };
+    let message = ApprovalMessage::new(&program_id, &proposal, &config, 3).unwrap();
+    let bytes = message.to_bytes().unwrap();
+    assert!(bytes.starts_with(ApprovalMessage::PREFIX));
+    assert_eq!(
+        bytes[ApprovalMessage::PREFIX.len()],
+        ApprovalMessage::VERSION
+    );
+    assert_eq!(ApprovalMessage::from_bytes(&bytes).unwrap(), message);
+    assert_eq!(
+        message.to_string(),
+        format!(
+            "Approve multisig proposal\n\
+             Version: 1\n\
+             Program: {}\n\
+             Group: {}\n\
+             Proposal: {}\n\
+             Config hash: {}\n\
+             Approval nonce: 3\n\
+             Expires: at slot 42",
+            program_id,
+            config.group,
+            proposal,
+            config.seed().unwrap()
+        )
+    );
+
+    // other proposals, configs and nonces give other messages
+    assert_ne!(
+        ApprovalMessage::new(&program_id, &proposal, &config, 4).unwrap(),
+        message
+    );
+    let mut config = config;
+    config.salt = 2;
+    assert_ne!(
+        ApprovalMessage::new(&program_id, &proposal, &config, 3).unwrap(),
+        message
+    );
+    // unknown versions and other data are rejected
+    let mut unknown = bytes.clone();
+    unknown[ApprovalMessage::PREFIX.len()] = ApprovalMessage::VERSION + 1;
+    assert!(ApprovalMessage::from_bytes(&unknown).is_err());
+    assert!(ApprovalMessage::from_bytes(&bytes[1..]).is_err());
+}
+
+#[test]
+fn ed25519_signers() {
+    let program_id = Pubkey::new_unique();
+    let alice = Keypair::new();
+    let bob = Keypair::new();
+    let proposal = Pubkey::new_unique();
+    let config = ProposalConfig {
+        group: Pubkey::new_unique(),
+        instructions: vec![],
+        author: alice.pubkey(),
+        salt: 1,
+        expiry: None,
+    };
+    let message = ApprovalMessage::new(&program_id, &proposal, &config, 0)
+        .unwrap()
+        .to_bytes()
+        .unwrap();
+    let signatures: Vec<(Pubkey, [u8; 64])> = [&alice, &bob]
+        .iter()
+        .map(|keypair| {
+            let mut signature = [0; 64];
+            signature.copy_from_slice(keypair.sign_message(&message).as_ref());
+            (keypair.pubkey(), signature)
+        })
+        .collect();
+
+    let instructions =
+        client::approve_with_signatures(&program_id, &proposal, &config, 0, &signatures);
+    let verify = &instructions[0];
+    assert_eq!(verify.program_id, ed25519::id());
+    assert_eq!(
+        ed25519::signers(verify, 0, &message),
+        Some(vec![alice.pubkey(), bob.pubkey()])
+    );
+
+    // signatures of another proposal don't count
+    let other = ApprovalMessage::new(&program_id, &Pubkey::new_unique(), &config, 0)
+        .unwrap()
+        .to_bytes()
+        .unwrap();
+    assert_eq!(ed25519::signers(verify, 0, &other), None);
+    // nor signatures made before an approval was revoked
+    let revoked = ApprovalMessage::new(&program_id, &proposal, &config, 1)
+        .unwrap()
+        .to_bytes()
+        .unwrap();
+    assert_eq!(ed25519::signers(verify, 0, &revoked), None);
+    // nor signed data taken from other instructions
+    let mut verify = verify.clone();
+    verify.data[4] = 1;
+    verify.data[5] = 0;
+    assert_eq!(ed25519::signers(&verify, 0, &message), None);
+    assert_eq!(
+        ed25519::signers(&verify, 1, &message),
+        Some(vec![alice.pubkey(), bob.pubkey()])
+    );
+}
+
+#[test]
+fn error_codes_are_stable() {
+    let table = vec![
+        (
+            0,
+            Error::InvalidInstructionData(std::io::ErrorKind::Other.into()),
+        ),
+        (1, Error::Serialize(std::io::ErrorKind::Other.into())),
+        (
+            2,
+            Error::AccountDataDeserialize(std::io::ErrorKind::Other.into()),
+        ),
+        (3, Error::ZeroThreshold),
+        (4, Error::TooManyMembers),
+        (5, Error::NoMembers),
+        (6, Error::ZeroWeight),
+        (7, Error::UnreachableThreshold),
+        (8, Error::InvalidGroupAccountKey),
+        (9, Error::InvalidProposalAccountKey),
+        (10, Error::InvalidProtectedAccountKey),
+        (11, Error::Unauthorized),
+        (12, Error::AlreadyParticipate),
+        (13, Error::InvalidAccountType),
+        (14, Error::EmptyAccountData),
+        (15, Error::ProposalExpired),
+        (16, Error::InvalidDestinationAccountKey),
+        (17, Error::ProposalNotApproved),
+        (18, Error::ExecutionDelayNotElapsed),
+        (19, Error::InvalidExecutionDelay),
+        (20, Error::ProposalAlreadyExecuted),
+        (21, Error::NotParticipating),
+        (22, Error::InvalidMemberIndex),
+        (23, Error::VaultAlreadyExists),
+        (24, Error::ProposalIsDraft),
+        (25, Error::ProposalIsNotDraft),
+        (26, Error::UnsupportedAccountVersion),
+        (27, Error::AccountNotMigrated),
+        (28, Error::InvalidSpendingLimitAccountKey),
+        (29, Error::InvalidSpendingLimitPeriod),
+        (30, Error::SpendingLimitExceeded),
+        (31, Error::InvalidTokenAccount),
+        (32, Error::ProgramNotAllowed),
+        (33, Error::InvalidSignatureInstruction),
+        (34, Error::ProposalClosed),
+        (35, Error::InvalidReceiptAccountKey),
+        (36, Error::StaleProposal),
+    ];
+    for (code, error) in table {
+        let decoded = Error::from_custom_code(code).unwrap();
+        assert_eq!(
+            std::mem::discriminant(&decoded),
+            std::mem::discriminant(&error)
+        );
+        assert_eq!(ProgramError::from(error), ProgramError::Custom(code));
+    }
+    assert!(Error::from_custom_code(37).is_none());
+
+    // Program errors are passed through as is.
+    assert_eq!(
+        ProgramError::from(Error::Program(ProgramError::MissingRequiredSignature)),
+        ProgramError::MissingRequiredSignature
+    );
+}
+
+#[test]
+fn proposal_receipt_layout() {
+    let receipt = ProposalReceipt {
+        group: Pubkey::new_unique(),
+        proposal: Pubkey::new_unique(),
+        bump: 255,
+        config_hash: hash(b"config"),
+        status: ProposalStatus::Executed,
+        slot: 42,
+        unix_timestamp: 1_600_000_000,
+    };
+    let mut data = vec![u8::from(AccountType::Receipt), ProposalReceipt::VERSION];
+    data.extend(receipt.try_to_vec().unwrap());
+    assert_eq!(data.len() as u64, client::receipt_account_space());
+    assert_eq!(
+        unpack_account_data::<ProposalReceipt>(AccountType::Receipt, &data).unwrap(),
+        receipt
+    );
+    assert!(matches!(
+        unpack_account_data::<ProposalHeader>(AccountType::Proposal, &data),
+        Err(Error::InvalidAccountType)
+    ));
+
+    // Ended proposals are closed to votes.
+    let mut state = ProposalState::new(1);
+    state.check_open_for_votes().unwrap();
+    for status in [
+        ProposalStatus::Rejected,
+        ProposalStatus::Cancelled,
+        ProposalStatus::Expired,
+    ] {
+        let mut data = state.try_to_vec().unwrap();
+        data[0] = status as u8;
+        state = ProposalState::try_from_slice(&data).unwrap();
+        assert_eq!(state.status(), status);
+        assert!(matches!(
+            state.check_open_for_votes(),
+            Err(Error::ProposalClosed)
+        ));
+        assert_eq!(state.approved_at(), None);
+    }
+}
+
+#[test]
+fn events_from_logs() {
+    let program_id = Pubkey::new_unique();
+    let other_program_id = Pubkey::new_unique();
+    let group = Pubkey::new_unique();
+    let proposal = Pubkey::new_unique();
+    let member = Pubkey::new_unique();
+
+    let approved = Event::Approved {
+        group,
+        proposal,
+        member,
+        weight: 1,
+        current_weight: 3,
+    };
+    let executed = Event::Executed { group, proposal };
+    let closed = Event::Closed {
+        proposal,
+        destination: member,
+        lamports: 100,
+    };
+    let log = |event: &Event| format!("Program log: {}", event.to_log().unwrap());
+    let logs = vec![
+        format!("Program {} invoke [1]", program_id),
+        "Program log: hello from multisig v3".to_owned(),
+        log(&approved),
+        log(&executed),
+        // Events logged by invoked programs are not trusted.
+        format!("Program {} invoke [2]", other_program_id),
+        log(&approved),
+        format!("Program {} success", other_program_id),
+        log(&closed),
+        format!("Program {} success", program_id),
+    ];
+
+    let events = Event::from_logs(&program_id, logs.iter().map(String::as_str)).unwrap();
+    assert_eq!(events, vec![approved.clone(), executed, closed]);
+    assert_eq!(Event::from_log(&log(&approved)).unwrap(), Some(approved));
+    assert_eq!(Event::from_log("Program log: hello").unwrap(), None);
+
+    // Unknown layout version.
+    let mut record = vec![crate::event::EVENT_VERSION + 1];
+    record.extend(Event::Executed { group, proposal }.try_to_vec().unwrap());
+    assert!(Event::from_log(&format!("multisig event: {}", base64::encode(record))).is_err());
+}
+
+#[tokio::test]
+async fn large_group() {
+    const MEMBER_COUNT: usize = 200;
+
+    let program_id = Pubkey::new_unique();
+    let members: Vec<Keypair> = (0..MEMBER_COUNT).map(|_| Keypair::new()).collect();
+    let destination_acc = Pubkey::new_unique();
+
+    let mut program_test = ProgramTest::new(env!("CARGO_PKG_NAME"), program_id, None);
+    program_test.prefer_bpf(true);
+    program_test.add_program(env!("CARGO_PKG_NAME"), program_id, None);
+
+    // Group is too large to fit init instruction into a transaction, so create the account directly.
+    let group_data = group_data(2, members.iter().map(|member| (member.pubkey(), 1)));
+    let seed = hash(&group_data.try_to_vec().unwrap());
+    let (group_account, bump) =
+        Pubkey::find_program_address(&[pda_tag::GROUP, seed.as_ref()], &program_id);
+    let (_, protected_bump) =
+        Pubkey::find_program_address(&[pda_tag::PROTECTED, group_account.as_ref()], &program_id);
+    let mut data = vec![u8::from(AccountType::Group), GroupAccount::VERSION];
+    data.extend(
+        GroupAccount {
+            seed,
+            bump,
+            vaults: vec![GroupVault {
+                index: 0,
+                bump: protected_bump,
+            }],
+            data: group_data,
+            proposal_count: 0,
+            config_version: 0,
+        }
+        .try_to_vec()
+        .unwrap(),
+    );
+    program_test.add_account(
+        group_account,
+        Account {
+            lamports: 1_000_000_000,
+            data,
+            owner: program_id,
+            ..Account::default()
+        },
+    );
+    let protected_account = protected_address(&program_id, &group_account);
+    program_test.add_account(
+        protected_account,
+        Account {
+            lamports: 100,
+            owner: SYSTEM_PROGRAM_ID,
+            ..Account::default()
+        },
+    );
+    let proposer = &members[MEMBER_COUNT - 1];
+    let approver = &members[100];
+    for member in &[proposer, approver] {
+        program_test.add_account(
+            member.pubkey(),
+            Account {
+                lamports: 1000,
+                owner: SYSTEM_PROGRAM_ID,
+                ..Account::default()
+            },
+        );
+    }
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    let proposed_instruction =
+        system_instruction::transfer(&protected_account, &destination_acc, 50);
+    let (mut transaction, proposal_acc) = do_propose(
+        program_id,
+        proposer.pubkey(),
+        payer.pubkey(),
+        group_account,
+        proposed_instruction,
+    );
+    transaction.sign(&[&payer, proposer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let proposal = banks_client
+        .get_account(proposal_acc)
+        .await
+        .unwrap()
+        .unwrap();
+    let proposal_data =
+        unpack_account_data::<ProposalData>(AccountType::Proposal, &proposal.data).unwrap();
+    assert!(proposal_data.state.is_approved_by(MEMBER_COUNT - 1));
+    let mut transaction = do_approve(
+        program_id,
+        approver.pubkey(),
+        payer.pubkey(),
+        proposal_data,
+        proposal_acc,
+    );
+    transaction.sign(&[approver, &payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let proposal = banks_client
+        .get_account(proposal_acc)
+        .await
+        .unwrap()
+        .unwrap();
+    let proposal_data =
+        unpack_account_data::<ProposalData>(AccountType::Proposal, &proposal.data).unwrap();
+    assert!(proposal_data.state.is_approved_by(100));
+    assert_eq!(proposal_data.state.status(), ProposalStatus::Approved);
+}
+
+#[tokio::test]
+async fn create_vault_and_transfer() {
+    const VAULT_LAMPORTS: u64 = 500;
+    const PROPOSED_LAMPORTS: u64 = 100;
+
+    let program_id = Pubkey::new_unique();
+    let alice = Keypair::new();
+    let alice_key = alice.pubkey();
+    let bob_key = Pubkey::new_unique();
+    let chris_key = Pubkey::new_unique();
+    let destination_acc = Pubkey::new_unique();
+
+    let mut program_test = ProgramTest::new(env!("CARGO_PKG_NAME"), program_id, None);
+    program_test.prefer_bpf(true);
+    program_test.add_program(env!("CARGO_PKG_NAME"), program_id, None);
+    program_test.add_account(
+        alice_key,
+        Account {
+            lamports: 1000,
+            owner: SYSTEM_PROGRAM_ID,
+            ..Account::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    // init with protected account
+    let (mut transaction, group_account) = do_init(
+        program_id,
+        2,
+        vec![(alice_key, 2), (bob_key, 1), (chris_key, 1)],
+        payer.pubkey(),
+        Some(ProtectedAccountConfig {
+            space: 0,
+            owner: SYSTEM_PROGRAM_ID,
+            lamports: 1000,
+        }),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let protected_account = vault_address(&program_id, &group_account, 0);
+    let vault_account = vault_address(&program_id, &group_account, 1);
+    assert_ne!(protected_account, vault_account);
+
+    // create vault 1 funded by the protected account
+    let proposed_instruction = client::create_vault(
+        &program_id,
+        &group_account,
+        &protected_account,
+        1,
+        ProtectedAccountConfig {
+            space: 0,
+            owner: SYSTEM_PROGRAM_ID,
+            lamports: VAULT_LAMPORTS,
+        },
+    );
+    let (mut transaction, proposal_acc) = do_propose(
+        program_id,
+        alice_key,
+        payer.pubkey(),
+        group_account,
+        proposed_instruction,
+    );
+    transaction.sign(&[&payer, &alice], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+    do_execute_proposal(&mut banks_client, &payer, program_id, proposal_acc).await;
+
+    let vault = banks_client
+        .get_account(vault_account)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(vault.lamports, VAULT_LAMPORTS);
+
+    // transfer from vault 1, signed by vault 1 only
+    let proposed_instruction = client::proposed_instruction(
+        system_instruction::transfer(&vault_account, &destination_acc, PROPOSED_LAMPORTS),
+        vec![1],
+    );
+    let (mut transaction, proposal_acc) = do_propose_signed_by(
+        program_id,
+        alice_key,
+        payer.pubkey(),
+        group_account,
+        proposed_instruction,
+        None,
+    );
+    transaction.sign(&[&payer, &alice], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+    do_execute_proposal(&mut banks_client, &payer, program_id, proposal_acc).await;
+
+    let destination = banks_client
+        .get_account(destination_acc)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(destination.lamports, PROPOSED_LAMPORTS);
+    let vault = banks_client
+        .get_account(vault_account)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(vault.lamports, VAULT_LAMPORTS - PROPOSED_LAMPORTS);
+}
+
+#[tokio::test]
+async fn nested_group_approval() {
+    const PROPOSED_LAMPORTS: u64 = 100;
+
+    let program_id = Pubkey::new_unique();
+    let alice = Keypair::new();
+    let alice_key = alice.pubkey();
+    let bob = Keypair::new();
+    let bob_key = bob.pubkey();
+    let destination_acc = Pubkey::new_unique();
+
+    let mut program_test = ProgramTest::new(env!("CARGO_PKG_NAME"), program_id, None);
+    program_test.prefer_bpf(true);
+    program_test.add_program(env!("CARGO_PKG_NAME"), program_id, None);
+    for key in [alice_key, bob_key] {
+        program_test.add_account(
+            key,
+            Account {
+                lamports: 1000,
+                owner: SYSTEM_PROGRAM_ID,
+                ..Account::default()
+            },
+        );
+    }
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let protected_config = || ProtectedAccountConfig {
+        space: 0,
+        owner: SYSTEM_PROGRAM_ID,
+        lamports: 1000,
+    };
+    // department group, run by bob alone
+    let (mut transaction, department) = do_init(
+        program_id,
+        1,
+        vec![(bob_key, 1)],
+        payer.pubkey(),
+        Some(protected_config()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+    // board group, with the department's protected account as a member
+    let department_protected = protected_address(&program_id, &department);
+    let (mut transaction, board) = do_init(
+        program_id,
+        2,
+        vec![(alice_key, 1), (department_protected, 1)],
+        payer.pubkey(),
+        Some(protected_config()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let board_protected = protected_address(&program_id, &board);
+    let (mut transaction, proposal_acc) = do_propose(
+        program_id,
+        alice_key,
+        payer.pubkey(),
+        board,
+        system_instruction::transfer(&board_protected, &destination_acc, PROPOSED_LAMPORTS),
+    );
+    transaction.sign(&[&payer, &alice], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    // the department approves by executing its own proposal
+    let (mut transaction, department_proposal) = do_propose_signed_by(
+        program_id,
+        bob_key,
+        payer.pubkey(),
+        department,
+        client::approve_as_group(&program_id, &department, &board, &proposal_acc),
+        None,
+    );
+    transaction.sign(&[&payer, &bob], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+    do_execute_proposal(&mut banks_client, &payer, program_id, department_proposal).await;
+
+    let proposal = banks_client
+        .get_account(proposal_acc)
+        .await
+        .unwrap()
+        .unwrap();
+    let proposal_data =
+        unpack_account_data::<ProposalData>(AccountType::Proposal, &proposal.data).unwrap();
+    assert_eq!(proposal_data.state.status(), ProposalStatus::Approved);
+    assert_eq!(proposal_data.state.current_weight(), 2);
+
+    let mut transaction = do_execute(
+        program_id,
+        payer.pubkey(),
+        payer.pubkey(),
+        proposal_data,
+        proposal_acc,
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let destination = banks_client
+        .get_account(destination_acc)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(destination.lamports, PROPOSED_LAMPORTS);
+}
+
+#[test]
+fn spending_limit_periods() {
+    let mut limit = SpendingLimit {
+        group: Pubkey::new_unique(),
+        index: 0,
+        bump: 255,
+        config: SpendingLimitConfig {
+            vault: 0,
+            mint: None,
+            amount: 100,
+            period: 60,
+            members: vec![],
+        },
+        period_start: 1000,
+        spent: 0,
+    };
+    limit.spend(60, 1000).unwrap();
+    limit.spend(40, 1059).unwrap();
+    assert!(matches!(
+        limit.spend(1, 1059),
+        Err(Error::SpendingLimitExceeded)
+    ));
+    // a new period starts once the current one is over
+    limit.spend(100, 1060).unwrap();
+    assert_eq!((limit.period_start, limit.spent), (1060, 100));
+    assert!(matches!(
+        limit.spend(u64::MAX, 2000),
+        Err(Error::SpendingLimitExceeded)
+    ));
+
+    let member = Pubkey::new_unique();
+    assert!(limit.config.allows(&member));
+    limit.config.members = vec![Pubkey::new_unique()];
+    assert!(!limit.config.allows(&member));
+}
+
+#[tokio::test]
+async fn spend_within_limit() {
+    const LIMIT: u64 = 300;
+
+    let program_id = Pubkey::new_unique();
+    let alice = Keypair::new();
+    let bob = Keypair::new();
+    let destination_acc = Pubkey::new_unique();
+
+    let mut program_test = ProgramTest::new(env!("CARGO_PKG_NAME"), program_id, None);
+    program_test.prefer_bpf(true);
+    program_test.add_program(env!("CARGO_PKG_NAME"), program_id, None);
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let (mut transaction, group_account) = do_init(
+        program_id,
+        2,
+        vec![(alice.pubkey(), 2), (bob.pubkey(), 1)],
+        payer.pubkey(),
+        Some(ProtectedAccountConfig {
+            space: 0,
+            owner: SYSTEM_PROGRAM_ID,
+            lamports: 1_000_000_000,
+        }),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    // only bob may spend up to LIMIT lamports a day from the protected account
+    let protected_account = protected_address(&program_id, &group_account);
+    let config = SpendingLimitConfig {
+        vault: 0,
+        mint: None,
+        amount: LIMIT,
+        period: 24 * 60 * 60,
+        members: vec![bob.pubkey()],
+    };
+    let proposed_instruction = client::create_spending_limit(
+        &program_id,
+        &group_account,
+        &protected_account,
+        0,
+        config.clone(),
+        10_000_000,
+    );
+    let (mut transaction, proposal_acc) = do_propose(
+        program_id,
+        alice.pubkey(),
+        payer.pubkey(),
+        group_account,
+        proposed_instruction,
+    );
+    transaction.sign(&[&payer, &alice], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+    do_execute_proposal(&mut banks_client, &payer, program_id, proposal_acc).await;
+
+    let spend = |signer: &Keypair, amount| {
+        let instruction = client::spend_within_limit(
+            &program_id,
+            &signer.pubkey(),
+            &group_account,
+            0,
+            &config,
+            &protected_account,
+            &destination_acc,
+            amount,
+        );
+        let mut transaction = Transaction::new_with_payer(&[instruction], Some(&payer.pubkey()));
+        transaction.sign(&[&payer, signer], recent_blockhash);
+        transaction
+    };
+
+    banks_client
+        .process_transaction(spend(&bob, LIMIT - 100))
+        .await
+        .unwrap();
+    let destination = banks_client
+        .get_account(destination_acc)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(destination.lamports, LIMIT - 100);
+
+    // over the limit for the period
+    assert!(banks_client
+        .process_transaction(spend(&bob, 101))
+        .await
+        .is_err());
+
+    // alice is a member, but not allowed by the limit
+    assert!(banks_client
+        .process_transaction(spend(&alice, 1))
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn spending_limit_needs_existing_vault() {
+    let program_id = Pubkey::new_unique();
+    let alice = Keypair::new();
+
+    let mut program_test = ProgramTest::new(env!("CARGO_PKG_NAME"), program_id, None);
+    program_test.prefer_bpf(true);
+    program_test.add_program(env!("CARGO_PKG_NAME"), program_id, None);
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let (mut transaction, group_account) = do_init(
+        program_id,
+        1,
+        vec![(alice.pubkey(), 1)],
+        payer.pubkey(),
+        Some(ProtectedAccountConfig {
+            space: 0,
+            owner: SYSTEM_PROGRAM_ID,
+            lamports: 1_000_000_000,
+        }),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    // vault 1 was never created
+    let protected_account = protected_address(&program_id, &group_account);
+    let proposed_instruction = client::create_spending_limit(
+        &program_id,
+        &group_account,
+        &protected_account,
+        0,
+        SpendingLimitConfig {
+            vault: 1,
+            mint: None,
+            amount: 100,
+            period: 60,
+            members: vec![],
+        },
+        10_000_000,
+    );
+    let (mut transaction, proposal_acc) = do_propose(
+        program_id,
+        alice.pubkey(),
+        payer.pubkey(),
+        group_account,
+        proposed_instruction,
+    );
+    transaction.sign(&[&payer, &alice], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let proposal = banks_client
+        .get_account(proposal_acc)
+        .await
+        .unwrap()
+        .unwrap();
+    let proposal_data =
+        unpack_account_data::<ProposalData>(AccountType::Proposal, &proposal.data).unwrap();
+    let mut transaction = do_execute(
+        program_id,
+        payer.pubkey(),
+        payer.pubkey(),
+        proposal_data,
+        proposal_acc,
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_err());
+
+    let limit_account = client::spending_limit_address(&program_id, &group_account, 0);
+    assert!(banks_client
+        .get_account(limit_account)
+        .await
+        .unwrap()
+        .is_none());
+}
+
+fn do_create_draft(
+    program_id: Pubkey,
+    signer: Pubkey,
+    payer: Pubkey,
+    group_account: Pubkey,
+    space: u64,
+    expiry: Option<ProposalExpiry>,
+) -> (Transaction, Pubkey) {
+    let proposal_key = proposal_address(&program_id, &group_account, 0);
+    let instruction = client::create_draft(
+        &program_id,
+        &signer,
+        &group_account,
+        0,
+        10_000,
+        space,
+        1,
+        expiry,
+    );
+    let transaction = Transaction::new_with_payer(&[instruction], Some(&payer));
+    (transaction, proposal_key)
+}
+
+fn do_append_to_draft(
+    program_id: Pubkey,
+    signer: Pubkey,
+    payer: Pubkey,
+    proposal_acc: Pubkey,
+    instructions: Vec<ProposedInstruction>,
+) -> Transaction {
+    let instruction = client::append_to_draft(&program_id, &signer, &proposal_acc, instructions);
+    Transaction::new_with_payer(&[instruction], Some(&payer))
+}
+
+fn do_finalize_draft(
+    program_id: Pubkey,
+    signer: Pubkey,
+    payer: Pubkey,
+    group_account: Pubkey,
+    proposal_acc: Pubkey,
+) -> Transaction {
+    let instruction = client::finalize_draft(&program_id, &signer, &group_account, &proposal_acc);
+    Transaction::new_with_payer(&[instruction], Some(&payer))
+}
+
+#[tokio::test]
+async fn draft_proposal() {
+    let program_id = Pubkey::new_unique();
+    let alice_key = Pubkey::new_unique();
+    let bob = Keypair::new();
+    let bob_key = bob.pubkey();
+    let chris = Keypair::new();
+    let chris_key = chris.pubkey();
+    let destination_acc = Pubkey::new_unique();
+
+    let mut program_test = ProgramTest::new(env!("CARGO_PKG_NAME"), program_id, None);
+    program_test.prefer_bpf(true);
+    program_test.add_program(env!("CARGO_PKG_NAME"), program_id, None);
+    for key in &[bob_key, chris_key] {
+        program_test.add_account(
+            *key,
+            Account {
+                lamports: 100_000,
+                owner: SYSTEM_PROGRAM_ID,
+                ..Account::default()
+            },
+        );
+    }
+
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    // init first
+    let (mut transaction, group_account) = do_init(
+        program_id,
+        2,
+        vec![(alice_key, 2), (bob_key, 1), (chris_key, 1)],
+        payer.pubkey(),
+        None,
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let protected_account = vault_address(&program_id, &group_account, 0);
+    do_transfer(&mut banks_client, &payer, protected_account, 100).await;
+
+    // create draft and fill it in two transactions
+    let (mut transaction, proposal_acc) = do_create_draft(
+        program_id,
+        bob_key,
+        payer.pubkey(),
+        group_account,
+        1000,
+        None,
+    );
+    transaction.sign(&[&payer, &bob], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    // the draft took the first proposal index of the group
+    let data = banks_client
+        .get_account(group_account)
+        .await
+        .unwrap()
+        .unwrap()
+        .data;
+    let group = unpack_account_data::<GroupAccount>(AccountType::Group, &data).unwrap();
+    assert_eq!(group.proposal_count, 1);
+
+    for lamports in &[30, 20] {
+        let instruction = client::proposed_instruction(
+            system_instruction::transfer(&protected_account, &destination_acc, *lamports),
+            vec![0],
+        );
+        let mut transaction = do_append_to_draft(
+            program_id,
+            bob_key,
+            payer.pubkey(),
+            proposal_acc,
+            vec![instruction],
+        );
+        transaction.sign(&[&payer, &bob], recent_blockhash);
+        banks_client.process_transaction(transaction).await.unwrap();
+    }
+
+    let proposal = banks_client
+        .get_account(proposal_acc)
+        .await
+        .unwrap()
+        .unwrap();
+    let proposal_data =
+        unpack_account_data::<ProposalData>(AccountType::Proposal, &proposal.data).unwrap();
+    assert_eq!(proposal_data.state.status(), ProposalStatus::Draft);
+    assert_eq!(proposal_data.config.instructions.len(), 2);
+
+    // draft can't be approved
+    let mut transaction = do_approve(
+        program_id,
+        chris_key,
+        payer.pubkey(),
+        proposal_data,
+        proposal_acc,
+    );
+    transaction.sign(&[&chris, &payer], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_err());
+
+    let mut transaction = do_finalize_draft(
+        program_id,
+        bob_key,
+        payer.pubkey(),
+        group_account,
+        proposal_acc,
+    );
+    transaction.sign(&[&payer, &bob], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    // finalized proposal can't be changed
+    let instruction = client::proposed_instruction(
+        system_instruction::transfer(&protected_account, &destination_acc, 50),
+        vec![0],
+    );
+    let mut transaction = do_append_to_draft(
+        program_id,
+        bob_key,
+        payer.pubkey(),
+        proposal_acc,
+        vec![instruction],
+    );
+    transaction.sign(&[&payer, &bob], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_err());
+
+    // now it can be approved and executed
+    let recent_blockhash = banks_client.get_recent_blockhash().await.unwrap();
+    let proposal = banks_client
+        .get_account(proposal_acc)
+        .await
+        .unwrap()
+        .unwrap();
+    let proposal_data =
+        unpack_account_data::<ProposalData>(AccountType::Proposal, &proposal.data).unwrap();
+    assert_eq!(proposal_data.state.status(), ProposalStatus::Active);
+    let mut transaction = do_approve(
+        program_id,
+        chris_key,
+        payer.pubkey(),
+        unpack_account_data::<ProposalData>(AccountType::Proposal, &proposal.data).unwrap(),
+        proposal_acc,
+    );
+    transaction.sign(&[&chris, &payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let mut transaction = do_execute(
+        program_id,
+        payer.pubkey(),
+        payer.pubkey(),
+        proposal_data,
+        proposal_acc,
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let destination = banks_client
+        .get_account(destination_acc)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(destination.lamports, 50);
+}
+
+#[tokio::test]
+async fn expired_draft() {
+    let program_id = Pubkey::new_unique();
+    let alice_key = Pubkey::new_unique();
+    let bob = Keypair::new();
+    let bob_key = bob.pubkey();
+    let destination_acc = Pubkey::new_unique();
+
+    let mut program_test = ProgramTest::new(env!("CARGO_PKG_NAME"), program_id, None);
+    program_test.prefer_bpf(true);
+    program_test.add_program(env!("CARGO_PKG_NAME"), program_id, None);
+    program_test.add_account(
+        bob_key,
+        Account {
+            lamports: 100_000,
+            owner: SYSTEM_PROGRAM_ID,
+            ..Account::default()
+        },
+    );
+
+    let mut context = program_test.start_with_context().await;
+    let payer = Keypair::from_bytes(&context.payer.to_bytes()).unwrap();
+    let recent_blockhash = context.last_blockhash;
+    // init first
+    let (mut transaction, group_account) = do_init(
+        program_id,
+        2,
+        vec![(alice_key, 2), (bob_key, 2)],
+        payer.pubkey(),
+        None,
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let protected_account = protected_address(&program_id, &group_account);
+
+    // create draft with expiry and fill it
+    let expiry_slot = context.banks_client.get_root_slot().await.unwrap() + 10;
+    let (mut transaction, proposal_acc) = do_create_draft(
+        program_id,
+        bob_key,
+        payer.pubkey(),
+        group_account,
+        1000,
+        Some(ProposalExpiry::Slot(expiry_slot)),
+    );
+    transaction.sign(&[&payer, &bob], recent_blockhash);
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let instruction = client::proposed_instruction(
+        system_instruction::transfer(&protected_account, &destination_acc, 50),
+        vec![0],
+    );
+    let mut transaction = do_append_to_draft(
+        program_id,
+        bob_key,
+        payer.pubkey(),
+        proposal_acc,
+        vec![instruction],
+    );
+    transaction.sign(&[&payer, &bob], recent_blockhash);
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    context.warp_to_slot(expiry_slot + 1).unwrap();
+    let recent_blockhash = context.banks_client.get_recent_blockhash().await.unwrap();
+
+    // bob's weight alone reaches the threshold, but the draft can't be finalized after expiry
+    let mut transaction = do_finalize_draft(
+        program_id,
+        bob_key,
+        payer.pubkey(),
+        group_account,
+        proposal_acc,
+    );
+    transaction.sign(&[&payer, &bob], recent_blockhash);
+    assert!(context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .is_err());
+
+    let proposal = context
+        .banks_client
+        .get_account(proposal_acc)
+        .await
+        .unwrap()
+        .unwrap();
+    let proposal_data =
+        unpack_account_data::<ProposalData>(AccountType::Proposal, &proposal.data).unwrap();
+    assert_eq!(proposal_data.state.status(), ProposalStatus::Draft);
+}
+
+#[tokio::test]
+async fn migrate_keeps_current_layout() {
+    let program_id = Pubkey::new_unique();
+    let alice = Keypair::new();
+
+    let mut program_test = ProgramTest::new(env!("CARGO_PKG_NAME"), program_id, None);
+    program_test.prefer_bpf(true);
+    program_test.add_program(env!("CARGO_PKG_NAME"), program_id, None);
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    let (mut transaction, group_account) = do_init(
+        program_id,
+        1,
+        vec![(alice.pubkey(), 1)],
+        payer.pubkey(),
+        None,
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+    let data_before = banks_client
+        .get_account(group_account)
+        .await
+        .unwrap()
+        .unwrap()
+        .data;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[client::migrate(&program_id, &group_account, None)],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let data = banks_client
+        .get_account(group_account)
+        .await
+        .unwrap()
+        .unwrap()
+        .data;
+    assert_eq!(data, data_before);
+    assert_eq!(data[1], GroupAccount::VERSION);
+}
+
+#[tokio::test]
+async fn migrate_grows_unversioned_group() {
+    let program_id = Pubkey::new_unique();
+    let alice = Pubkey::new_unique();
+
+    // Group account of the first program release, sized to fit its data exactly.
+    let mut data = vec![1, 1, 0, 0, 0];
+    data.extend(alice.as_ref());
+    data.extend([1, 0, 0, 0, 1, 0, 0, 0]);
+    let seed = hash(&data[1..]);
+    let (group_account, _) =
+        Pubkey::find_program_address(&[pda_tag::GROUP, seed.as_ref()], &program_id);
+
+    let mut program_test = ProgramTest::new(env!("CARGO_PKG_NAME"), program_id, None);
+    program_test.prefer_bpf(true);
+    program_test.add_program(env!("CARGO_PKG_NAME"), program_id, None);
+    program_test.add_account(
+        group_account,
+        Account {
+            lamports: Rent::default().minimum_balance(data.len()),
+            data,
+            owner: program_id,
+            ..Account::default()
+        },
+    );
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[client::migrate(
+            &program_id,
+            &group_account,
+            Some(&payer.pubkey()),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let account = banks_client
+        .get_account(group_account)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        account.lamports,
+        Rent::default().minimum_balance(account.data.len())
+    );
+    assert_eq!(account.data[0], u8::from(AccountType::Group));
+    assert_eq!(account.data[1], GroupAccount::VERSION);
+    let group = unpack_account_data::<GroupAccount>(AccountType::Group, &account.data).unwrap();
+    assert_eq!(group.seed, seed);
+    assert_eq!(group.data.members[0].public_key, alice);
+    assert_eq!(group.data.threshold, 1);
+}
+
+#[test]
+fn execution_delay_fits_timestamps() {
+    let mut group = group_data(1, vec![(Pubkey::new_unique(), 1)]);
+    group.execution_delay = i64::MAX as u64;
+    group.validate().unwrap();
+    // A larger delay would wrap to a negative one and let proposals execute right away.
+    group.execution_delay = i64::MAX as u64 + 1;
+    assert!(matches!(
+        group.validate(),
+        Err(Error::InvalidExecutionDelay)
+    ));
+    group.execution_delay = u64::MAX;
+    assert!(matches!(
+        group.validate(),
+        Err(Error::InvalidExecutionDelay)
+    ));
+}
@@ -0,0 +1,41 @@
+//! Minimal SPL Token interface, just enough to spend tokens held by group vaults.
+
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+};
+
+solana_program::declare_id!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+
+/// Tag of the token program `Transfer` instruction.
+const TRANSFER: u8 = 3;
+/// Size of a token account. Mints and multisigs have other sizes.
+const ACCOUNT_LEN: usize = 165;
+
+/// Token program instruction moving `amount` tokens from `source` to `destination`.
+pub fn transfer(
+    source: &Pubkey,
+    destination: &Pubkey,
+    authority: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    let mut data = vec![TRANSFER];
+    data.extend_from_slice(&amount.to_le_bytes());
+    Instruction {
+        program_id: id(),
+        accounts: vec![
+            AccountMeta::new(*source, false),
+            AccountMeta::new(*destination, false),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+        data,
+    }
+}
+
+/// Mint and owner of a token account, which are the first fields of its data.
+pub fn account_mint_and_owner(data: &[u8]) -> Option<(Pubkey, Pubkey)> {
+    if data.len() != ACCOUNT_LEN {
+        return None;
+    }
+    Some((Pubkey::new(&data[..32]), Pubkey::new(&data[32..64])))
+}
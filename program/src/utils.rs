@@ -1,46 +1,87 @@
 use crate::error::Error;
 
-use solana_program::{account_info::AccountInfo, program_error::ProgramError};
+use solana_program::{
+    account_info::AccountInfo, entrypoint::MAX_PERMITTED_DATA_INCREASE, program_error::ProgramError,
+};
 
-use crate::state::AccountType;
-use borsh::{BorshDeserialize, BorshSerialize};
+use crate::state::{unpack_account_data, AccountType, Versioned, ACCOUNT_HEADER_LEN};
 
-pub fn read_account_data<T: BorshDeserialize>(
+pub fn read_account_data<T: Versioned>(
     account_type: AccountType,
     info: &AccountInfo,
 ) -> Result<T, Error> {
-    let buf = info.data.borrow();
-    if buf.is_empty() {
-        return Err(Error::EmptyAccountData);
-    }
-    if buf[0] != u8::from(account_type) {
-        return Err(Error::InvalidAccountType);
-    }
-    T::try_from_slice(&buf[1..]).map_err(Error::AccountDataDeserialize)
+    unpack_account_data(account_type, &info.data.borrow())
 }
 
 pub fn write_serialized_data(
     info: &AccountInfo,
     account_type: AccountType,
+    version: u8,
     data: &[u8],
 ) -> Result<(), Error> {
     let mut buf = info.data.borrow_mut();
-    if buf.len() < data.len() + 1 {
+    if buf.len() < data.len() + ACCOUNT_HEADER_LEN {
         return Err(ProgramError::AccountDataTooSmall.into());
     }
     buf[0] = account_type.into();
-    buf[1..data.len() + 1].copy_from_slice(data);
-    for x in &mut buf[data.len() + 1..] {
+    buf[1] = version;
+    buf[ACCOUNT_HEADER_LEN..data.len() + ACCOUNT_HEADER_LEN].copy_from_slice(data);
+    for x in &mut buf[data.len() + ACCOUNT_HEADER_LEN..] {
         *x = 0; // TODO: use `fill()` (need Rust 1.50)
     }
     Ok(())
 }
 
-pub fn write_account_data<T: BorshSerialize>(
+pub fn write_account_data<T: Versioned>(
     info: &AccountInfo,
     account_type: AccountType,
     data: &T,
 ) -> Result<(), Error> {
     let data = data.try_to_vec().map_err(Error::Serialize)?;
-    write_serialized_data(info, account_type, &data)
+    write_serialized_data(info, account_type, T::VERSION, &data)
+}
+
+/// Overwrite the head of account data with `data`, leaving the bytes after it untouched.
+/// The rest of the data has to be in the current layout already, so the account is not migrated.
+pub fn write_account_header<T: Versioned>(
+    info: &AccountInfo,
+    account_type: AccountType,
+    data: &T,
+) -> Result<(), Error> {
+    let data = data.try_to_vec().map_err(Error::Serialize)?;
+    let mut buf = info.data.borrow_mut();
+    if buf.len() < data.len() + ACCOUNT_HEADER_LEN {
+        return Err(ProgramError::AccountDataTooSmall.into());
+    }
+    if buf[0] != u8::from(account_type) || buf[1] != T::VERSION {
+        return Err(Error::AccountNotMigrated);
+    }
+    buf[ACCOUNT_HEADER_LEN..data.len() + ACCOUNT_HEADER_LEN].copy_from_slice(&data);
+    Ok(())
+}
+
+/// Grow account data to `len` bytes. The runtime leaves room for [MAX_PERMITTED_DATA_INCREASE]
+/// more bytes after the data of each account and picks up the data length stored right before
+/// the data, which is how newer runtimes implement `AccountInfo::realloc`.
+pub fn grow_account_data(info: &AccountInfo, len: usize) -> Result<(), Error> {
+    let mut data = info.data.borrow_mut();
+    let old_len = data.len();
+    if len <= old_len {
+        return Ok(());
+    }
+    // Native programs, e.g. in tests, get account data with no room after it.
+    if len - old_len > MAX_PERMITTED_DATA_INCREASE || !cfg!(target_arch = "bpf") {
+        return Err(ProgramError::AccountDataTooSmall.into());
+    }
+    // SAFETY: the runtime serializes the data length as u64 right before the data and reserves
+    // the bytes after it, see `solana_program::entrypoint::deserialize`.
+    unsafe {
+        let ptr = data.as_mut_ptr();
+        (ptr.offset(-8) as *mut u64).write_unaligned(len as u64);
+        *data = std::slice::from_raw_parts_mut(ptr, len);
+    }
+    for x in &mut data[old_len..] {
+        *x = 0;
+    }
+    Ok(())
 }